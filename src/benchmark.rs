@@ -0,0 +1,116 @@
+use std::time::{Instant};
+
+use rand::prelude::*;
+
+use crate::game::game::*;
+use crate::game::genome::{GenomeKind};
+use crate::game::islands::*;
+use crate::game::optimizer::*;
+use crate::game::online::*;
+use crate::game::pilot::*;
+use crate::game::solver::*;
+use crate::parsing::parser::{LevelData};
+use crate::parsing::settings::{Settings};
+
+// runs the same search (same seed) on one thread then on the whole pool,
+// both runs must find the same ships, only the time should change
+pub fn run(level_data: &LevelData, settings: &Settings, generations: usize) {
+    let mut settings = settings.clone();
+    let seed = settings.seed.unwrap_or_else(|| rand::thread_rng().gen());
+    settings.seed = Some(seed);
+    let mut thread_counts = vec![1, rayon::current_num_threads()];
+    thread_counts.dedup();
+
+    println!("benchmark: {} generations of {} ships | optimizer: {:?} | seed: {}", generations, settings.population_count, settings.optimizer, seed);
+    let mut serial_ms = 0.0;
+    for threads in thread_counts {
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().expect("error: can't build the thread pool");
+        let mut game: Game = Game::setup(level_data, &island_settings(&settings, 0));
+        let mut optimizer = settings.optimizer.build(level_data, &settings);
+        game.verbose = false;
+        let start_time = Instant::now();
+        let mut flown = 0;
+        pool.install(|| {
+            for _ in 0..generations {
+                flown += optimizer.step(&mut game);
+            }
+        });
+        let ms = start_time.elapsed().as_secs_f64() * 1000.0;
+        if threads == 1 {
+            serial_ms = ms;
+        }
+        let ships_per_second = flown as f64 / (ms / 1000.0);
+        println!("threads: {:>3} | {:>8.1} ms | {:>9.0} ships/s | x{:.2} | best fitness: {}",
+            threads, ms, ships_per_second, serial_ms / ms, game.best_fitness);
+    }
+}
+
+// runs each optimizer (same seed) until it has flown `evaluations` ships,
+// the last step may overshoot by less than a batch
+pub fn compare(level_data: &LevelData, settings: &Settings, evaluations: usize) {
+    let mut settings = settings.clone();
+    let seed = settings.seed.unwrap_or_else(|| rand::thread_rng().gen());
+    settings.seed = Some(seed);
+    let optimizers = [OptimizerKind::Ga, OptimizerKind::Random, OptimizerKind::Annealing, OptimizerKind::Cmaes, OptimizerKind::De, OptimizerKind::Beam];
+
+    println!("compare: {} ships per optimizer | batches of {} ships | seed: {}", evaluations, settings.population_count, seed);
+    let game: Game = Game::setup(level_data, &island_settings(&settings, 0));
+    let pilot = fly_pilot(&game, settings.pilot_clearance, settings.policy_max_turns);
+    println!("{:>10} | {:>7} ships | landed: {} | fuel: {} | fitness: {}", "pilot", 1, pilot.is_solution, pilot.fuel, pilot.chromosome.fitness);
+    // the landings are given as a gap to the fuel left by the solver
+    let reference = match solve(&game) {
+        Ok(solution) => {
+            println!("{:>10} | {:>7} states | fuel: {} | {} turns", "solver", solution.expanded, solution.fuel, solution.commands.len());
+            Some(solution.fuel)
+        },
+        Err(error) => {
+            println!("{:>10} | {}", "solver", error);
+            None
+        }
+    };
+    for kind in optimizers.iter() {
+        if *kind == OptimizerKind::Beam && settings.encoding == GenomeKind::Segments {
+            println!("{:>10} | skipped, it needs one gene per turn (delta or targets encoding)", "beam");
+            continue;
+        }
+        let mut game: Game = Game::setup(level_data, &island_settings(&settings, 0));
+        let mut optimizer = kind.build(level_data, &settings);
+        game.verbose = false;
+        let start_time = Instant::now();
+        let mut flown = 0;
+        while flown < evaluations {
+            flown += optimizer.step(&mut game);
+        }
+        let ms = start_time.elapsed().as_secs_f64() * 1000.0;
+        let gap = match (&game.best_ship, reference) {
+            (Some(best), Some(reference)) => format!(" | fuel: {} | gap: {}", best.fuel, reference - best.fuel),
+            (Some(best), None) => format!(" | fuel: {}", best.fuel),
+            _ => String::new()
+        };
+        println!("{:>10} | {:>7} ships | {:>8.1} ms | best fitness: {}{}",
+            format!("{:?}", kind).to_lowercase(), flown, ms, game.best_fitness, gap);
+    }
+}
+
+// flies the level turn by turn, replanning on each turn (see play_online),
+// against the plan of the first turn flown to the end
+pub fn online(level_data: &LevelData, settings: &Settings, first_ms: u128) {
+    let mut settings = settings.clone();
+    let seed = settings.seed.unwrap_or_else(|| rand::thread_rng().gen());
+    settings.seed = Some(seed);
+
+    println!("online: {} ms on the first turn then {} ms per turn | optimizer: {:?} | referee: {:?} | noise: {} | seed: {}",
+        first_ms, settings.online_turn_ms, settings.optimizer, settings.online_referee, settings.online_noise, seed);
+    let start_time = Instant::now();
+    let flight = play_online(level_data, &settings, first_ms, settings.online_turn_ms as u128);
+    let ms = start_time.elapsed().as_secs_f64() * 1000.0;
+    for (name, ship) in [("one-shot", &flight.one_shot), ("online", &flight.ship)].iter() {
+        println!("{:>10} | landed: {} | fuel: {} | {} turns", name, ship.is_solution, ship.fuel, ship.path.len());
+    }
+    println!("{:>10} | {} steps | {:.1} ms | resyncs: {} | max drift: {:.2}", "search", flight.steps, ms, flight.resyncs, flight.max_drift());
+    if !flight.drifts.is_empty() {
+        let turns = flight.drifts.len() as f32;
+        let (position, speed) = flight.drifts.iter().fold((0.0, 0.0), |sum, drift| (sum.0 + drift.0, sum.1 + drift.1));
+        println!("{:>10} | mean {:.2} m, {:.2} m/s | {} elites flown again, {} dropped", "drift", position / turns, speed / turns, flight.elites.0, flight.elites.1);
+    }
+}
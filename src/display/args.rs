@@ -8,7 +8,7 @@ pub fn handle_args(e: &Event, game: &mut Game) {
             Button::Keyboard(Key::Space) => {
                 game.paused = !game.paused;
             },
-            Button::Keyboard(Key::Right) => if game.paused {
+            Button::Keyboard(Key::Right) if game.paused => {
                 game.next_turn = true;
             },
             // Button::Keyboard(Key::Left) => {
@@ -3,7 +3,7 @@ extern crate graphics;
 extern crate opengl_graphics;
 extern crate piston;
 
-use piston::window::WindowSettings;
+use piston::window::{WindowSettings, AdvancedWindow};
 use piston::input::*;
 use glutin_window::GlutinWindow;
 use opengl_graphics::{GlGraphics, OpenGL};
@@ -38,18 +38,22 @@ impl Display {
             .expect("error: can't initialize the GlutinWindow");
         return Display {
             window_space: Space::new(0.0, window_w, window_h, 0.0),
-            window: window,
+            window,
             gl: GlGraphics::new(opengl),
         };
     }
 
+    pub fn set_status(&mut self, status: &str) {
+        self.window.set_title(format!("Mars Lander Simulator | {}", status));
+    }
+
     pub fn clear_window(&mut self, event: &RenderArgs) {
         self.gl.draw(event.viewport(), |_context, gl| {
             graphics::clear(GREY1, gl);
         });
     }
 
-    pub fn render_ground(&mut self, event: &RenderArgs, map: &Vec<Pos>) {
+    pub fn render_ground(&mut self, event: &RenderArgs, map: &[Pos]) {
         let window_space = &self.window_space;
         self.gl.draw(event.viewport(), |c, gl| {
             for index in 0..(map.len() - 1) {
@@ -92,7 +96,7 @@ impl Display {
     pub fn render_ray(&mut self, event: &RenderArgs, ship: &Ship, color: [f32; 4]) {
         let window_space = &self.window_space;
         self.gl.draw(event.viewport(), |c, gl| {
-            if ship.path.len() > 0 {
+            if !ship.path.is_empty() {
                 for i in 0..(ship.path.len() - 1) {
                     let (x0, y0) = (ship.path[i].scale(window_space).x, ship.path[i].scale(window_space).y);
                     let (x1, y1) = (ship.path[i + 1].scale(window_space).x, ship.path[i + 1].scale(window_space).y);
@@ -1,2 +1,3 @@
+#[allow(clippy::module_inception)]
 pub mod display;
 pub mod args;
\ No newline at end of file
@@ -0,0 +1,139 @@
+use rand_distr::{Distribution, StandardNormal};
+
+use super::game::*;
+use super::genome::*;
+use super::optimizer::*;
+use crate::parsing::settings::{Settings};
+
+/* --------------------------------------------------------- */
+/* -   SEPARABLE CMA-ES   ---------------------------------- */
+/* --------------------------------------------------------- */
+
+// CMA-ES with a diagonal covariance (Ros and Hansen, 2008): memory and time are
+// linear in the number of genes, it searches the chromosome as a vector of [0, 1]
+// values (see to_unit), the population_count ships of a step are its lambda samples
+pub struct SepCmaEs {
+    pub mean: Vec<f32>,
+    pub sigma: f32,
+    pub variances: Vec<f32>, // diagonal of the covariance
+    pub path_sigma: Vec<f32>,
+    pub path_c: Vec<f32>,
+    pub weights: Vec<f32>,
+    pub generation: usize
+}
+
+impl SepCmaEs {
+    pub fn new(settings: &Settings) -> Self {
+        let n = settings.chromosome_size * 2;
+        let mu = (settings.population_count.max(4) / 2).max(1);
+        let raw = (0..mu).map(|i| (mu as f32 + 0.5).ln() - ((i + 1) as f32).ln()).collect::<Vec<f32>>();
+        let total: f32 = raw.iter().sum();
+        return Self {
+            mean: vec![0.5; n],
+            sigma: settings.cmaes_sigma,
+            variances: vec![1.0; n],
+            path_sigma: vec![0.0; n],
+            path_c: vec![0.0; n],
+            weights: raw.iter().map(|w| w / total).collect::<Vec<f32>>(),
+            generation: 0
+        };
+    }
+}
+
+impl Optimizer for SepCmaEs {
+    fn step(&mut self, game: &mut Game) -> usize {
+        let bounds = game.genome.bounds();
+        let n = self.mean.len();
+        let lambda = game.settings.population_count.max(4);
+        let mu = self.weights.len();
+        let nf = n as f32;
+        let mu_eff = 1.0 / self.weights.iter().map(|w| w * w).sum::<f32>();
+        let c_sigma = (mu_eff + 2.0) / (nf + mu_eff + 5.0);
+        let d_sigma = 1.0 + 2.0 * (((mu_eff - 1.0) / (nf + 1.0)).sqrt() - 1.0).max(0.0) + c_sigma;
+        let c_c = (4.0 + mu_eff / nf) / (nf + 4.0 + 2.0 * mu_eff / nf);
+        // the diagonal learns (n + 2) / 3 times faster than a full covariance
+        let c_1 = (2.0 / ((nf + 1.3).powi(2) + mu_eff) * (nf + 2.0) / 3.0).min(1.0);
+        let c_mu = (2.0 * (mu_eff - 2.0 + 1.0 / mu_eff) / ((nf + 2.0).powi(2) + mu_eff) * (nf + 2.0) / 3.0).min(1.0 - c_1);
+        let chi_n = nf.sqrt() * (1.0 - 1.0 / (4.0 * nf) + 1.0 / (21.0 * nf * nf));
+
+        // samples: x = mean + sigma * D * z
+        let deviations = self.variances.iter().map(|v| v.sqrt()).collect::<Vec<f32>>();
+        let samples = (0..lambda).map(|_| {
+            (0..n).map(|j| {
+                let z: f32 = StandardNormal.sample(&mut game.rng);
+                deviations[j] * z
+            }).collect::<Vec<f32>>()
+        }).collect::<Vec<Vec<f32>>>();
+        game.ships = samples.iter().map(|y| {
+            let x = self.mean.iter().zip(y.iter()).map(|(m, y)| m + self.sigma * y).collect::<Vec<f32>>();
+            Ship::from_chromosome(&game.level_data, Chromosome::from_genes(from_unit(&x, &bounds)))
+        }).collect::<Vec<Ship>>();
+        game.fly_population();
+        game.previous_population = game.ships.clone();
+        game.generation += 1;
+
+        let mut order = (0..lambda).collect::<Vec<usize>>();
+        order.sort_by(|a, b| game.ships[*b].chromosome.fitness.total_cmp(&game.ships[*a].chromosome.fitness));
+        let mut y_w = vec![0.0; n];
+        for (rank, index) in order.iter().take(mu).enumerate() {
+            for j in 0..n {
+                y_w[j] += self.weights[rank] * samples[*index][j];
+            }
+        }
+        for (mean, y) in self.mean.iter_mut().zip(y_w.iter()) {
+            *mean += self.sigma * y;
+        }
+
+        self.generation += 1;
+        let path_factor = (c_sigma * (2.0 - c_sigma) * mu_eff).sqrt();
+        for j in 0..n {
+            self.path_sigma[j] = (1.0 - c_sigma) * self.path_sigma[j] + path_factor * y_w[j] / deviations[j];
+        }
+        let norm = self.path_sigma.iter().map(|p| p * p).sum::<f32>().sqrt();
+        let h_sigma = norm / (1.0 - (1.0 - c_sigma).powi(2 * self.generation as i32)).sqrt() < (1.4 + 2.0 / (nf + 1.0)) * chi_n;
+        let h_sigma = if h_sigma { 1.0 } else { 0.0 };
+        let c_factor = (c_c * (2.0 - c_c) * mu_eff).sqrt();
+        for j in 0..n {
+            self.path_c[j] = (1.0 - c_c) * self.path_c[j] + h_sigma * c_factor * y_w[j];
+            let rank_mu = order.iter().take(mu).enumerate().map(|(rank, index)| self.weights[rank] * samples[*index][j] * samples[*index][j]).sum::<f32>();
+            let rank_one = self.path_c[j] * self.path_c[j] + (1.0 - h_sigma) * c_c * (2.0 - c_c) * self.variances[j];
+            self.variances[j] = ((1.0 - c_1 - c_mu) * self.variances[j] + c_1 * rank_one + c_mu * rank_mu).max(1e-12);
+        }
+        self.sigma *= ((c_sigma / d_sigma) * (norm / chi_n - 1.0)).exp();
+        self.sigma = self.sigma.clamp(1e-6, 1.0);
+        if game.verbose {
+            eprintln!("    cma-es: sigma {:.4}", self.sigma);
+        }
+        return lambda;
+    }
+
+    // the values of the genes flown leave the front, the new tail starts
+    // in the middle of its bounds with a unit variance
+    fn advance(&mut self, game: &mut Game, observed: &Ship) {
+        let bounds = game.genome.bounds();
+        let played = game.genome.genes_played(&from_unit(&self.mean, &bounds), 1);
+        for values in [&mut self.mean, &mut self.variances, &mut self.path_sigma, &mut self.path_c].iter_mut() {
+            values.drain(0..(played * 2).min(values.len()));
+        }
+        while self.mean.len() < game.settings.chromosome_size * 2 {
+            self.mean.push(0.5);
+            self.variances.push(1.0);
+            self.path_sigma.push(0.0);
+            self.path_c.push(0.0);
+        }
+        game.advance(observed);
+    }
+
+    // the mean moves to the best elite kept, the evolution paths led to the old mean
+    fn resync(&mut self, game: &mut Game, observed: &Ship) -> (usize, usize) {
+        let (kept, dropped) = advance_and_resync(self, game, observed);
+        if let Some(best) = fittest(&kept) {
+            let mut mean = to_unit(&best.chromosome.genes, &game.genome.bounds());
+            mean.resize(self.mean.len(), 0.5);
+            self.mean = mean;
+            self.path_sigma.iter_mut().for_each(|p| *p = 0.0);
+            self.path_c.iter_mut().for_each(|p| *p = 0.0);
+        }
+        return (kept.len(), dropped);
+    }
+}
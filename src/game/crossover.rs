@@ -0,0 +1,283 @@
+use std::str::FromStr;
+
+use rand::prelude::*;
+use serde::Deserialize;
+
+use super::game::*;
+use super::genome::*;
+use crate::parsing::settings::{Settings};
+
+pub trait Crossover: Send + Sync {
+    // returns the genes of the two childs
+    fn cross(&self, a: &Ship, b: &Ship, rng: &mut StdRng) -> [Vec<(i32, i32)>; 2];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CrossoverKind {
+    Blend,
+    Arithmetic,
+    SinglePoint,
+    TwoPoint,
+    Uniform,
+    Death,
+    CutSplice
+}
+
+impl FromStr for CrossoverKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s {
+            "blend" => Ok(CrossoverKind::Blend),
+            "arithmetic" => Ok(CrossoverKind::Arithmetic),
+            "single_point" => Ok(CrossoverKind::SinglePoint),
+            "two_point" => Ok(CrossoverKind::TwoPoint),
+            "uniform" => Ok(CrossoverKind::Uniform),
+            "death" => Ok(CrossoverKind::Death),
+            "cut_splice" => Ok(CrossoverKind::CutSplice),
+            _ => Err(format!("error: unknown crossover operator: {}", s))
+        };
+    }
+}
+
+impl CrossoverKind {
+    pub fn build(&self, settings: &Settings) -> Box<dyn Crossover> {
+        return match self {
+            CrossoverKind::Blend => Box::new(Blend),
+            CrossoverKind::Arithmetic => Box::new(Arithmetic { weight: settings.crossover_weight }),
+            CrossoverKind::SinglePoint => Box::new(SinglePoint),
+            CrossoverKind::TwoPoint => Box::new(TwoPoint),
+            CrossoverKind::Uniform => Box::new(Uniform),
+            CrossoverKind::Death => Box::new(Death { backoff: settings.crossover_death_backoff, genome: settings.encoding.build(settings) }),
+            CrossoverKind::CutSplice => Box::new(CutSplice)
+        };
+    }
+}
+
+fn mix(a: (i32, i32), b: (i32, i32), r: f32) -> (i32, i32) {
+    return (
+        (r * a.0 as f32 + (1.0 - r) * b.0 as f32).round() as i32,
+        (r * a.1 as f32 + (1.0 - r) * b.1 as f32).round() as i32
+    );
+}
+
+// parents may not have the same length, the genes of the longest past the end of
+// the other are kept as they are
+
+// childs take `a` genes before `cut` and `b` genes after, and the opposite
+fn cut_at(a: &[(i32, i32)], b: &[(i32, i32)], cut: usize) -> [Vec<(i32, i32)>; 2] {
+    let cut = cut.min(a.len()).min(b.len());
+    let mut child_a = a[..cut].to_vec();
+    child_a.extend_from_slice(&b[cut..]);
+    let mut child_b = b[..cut].to_vec();
+    child_b.extend_from_slice(&a[cut..]);
+    return [child_a, child_b];
+}
+
+// the first child keeps the length of `a`, the second the length of `b`
+fn extend_tails(childs: &mut [Vec<(i32, i32)>; 2], a: &Ship, b: &Ship) {
+    let size = childs[0].len();
+    childs[0].extend_from_slice(&a.chromosome.genes[size..]);
+    childs[1].extend_from_slice(&b.chromosome.genes[size..]);
+}
+
+/* --------------------------------------------------------- */
+/* -   BLEND   --------------------------------------------- */
+/* --------------------------------------------------------- */
+
+// arithmetic blend with a new random weight for each gene
+pub struct Blend;
+
+impl Crossover for Blend {
+    fn cross(&self, a: &Ship, b: &Ship, rng: &mut StdRng) -> [Vec<(i32, i32)>; 2] {
+        let mut childs: [Vec<(i32, i32)>; 2] = [vec![], vec![]];
+        for (gene_a, gene_b) in a.chromosome.genes.iter().zip(b.chromosome.genes.iter()) {
+            let r: f32 = rng.gen();
+            childs[0].push(mix(*gene_a, *gene_b, r));
+            childs[1].push(mix(*gene_a, *gene_b, 1.0 - r));
+        }
+        extend_tails(&mut childs, a, b);
+        return childs;
+    }
+}
+
+/* --------------------------------------------------------- */
+/* -   ARITHMETIC   ---------------------------------------- */
+/* --------------------------------------------------------- */
+
+pub struct Arithmetic {
+    pub weight: f32
+}
+
+impl Crossover for Arithmetic {
+    fn cross(&self, a: &Ship, b: &Ship, _rng: &mut StdRng) -> [Vec<(i32, i32)>; 2] {
+        let genes = a.chromosome.genes.iter().zip(b.chromosome.genes.iter());
+        let mut childs = [
+            genes.clone().map(|(gene_a, gene_b)| mix(*gene_a, *gene_b, self.weight)).collect::<Vec<(i32, i32)>>(),
+            genes.map(|(gene_a, gene_b)| mix(*gene_a, *gene_b, 1.0 - self.weight)).collect::<Vec<(i32, i32)>>()
+        ];
+        extend_tails(&mut childs, a, b);
+        return childs;
+    }
+}
+
+/* --------------------------------------------------------- */
+/* -   SINGLE POINT   -------------------------------------- */
+/* --------------------------------------------------------- */
+
+pub struct SinglePoint;
+
+impl Crossover for SinglePoint {
+    fn cross(&self, a: &Ship, b: &Ship, rng: &mut StdRng) -> [Vec<(i32, i32)>; 2] {
+        let cut = rng.gen_range(0..=a.chromosome.genes.len());
+        return cut_at(&a.chromosome.genes, &b.chromosome.genes, cut);
+    }
+}
+
+/* --------------------------------------------------------- */
+/* -   TWO POINT   ----------------------------------------- */
+/* --------------------------------------------------------- */
+
+pub struct TwoPoint;
+
+impl Crossover for TwoPoint {
+    fn cross(&self, a: &Ship, b: &Ship, rng: &mut StdRng) -> [Vec<(i32, i32)>; 2] {
+        let size = a.chromosome.genes.len().min(b.chromosome.genes.len());
+        let (mut start, mut end) = (rng.gen_range(0..=size), rng.gen_range(0..=size));
+        if start > end {
+            std::mem::swap(&mut start, &mut end);
+        }
+        let mut childs = [a.chromosome.genes.clone(), b.chromosome.genes.clone()];
+        childs[0][start..end].copy_from_slice(&b.chromosome.genes[start..end]);
+        childs[1][start..end].copy_from_slice(&a.chromosome.genes[start..end]);
+        return childs;
+    }
+}
+
+/* --------------------------------------------------------- */
+/* -   UNIFORM   ------------------------------------------- */
+/* --------------------------------------------------------- */
+
+pub struct Uniform;
+
+impl Crossover for Uniform {
+    fn cross(&self, a: &Ship, b: &Ship, rng: &mut StdRng) -> [Vec<(i32, i32)>; 2] {
+        let mut childs = [a.chromosome.genes.clone(), b.chromosome.genes.clone()];
+        for (i, (gene_a, gene_b)) in a.chromosome.genes.iter().zip(b.chromosome.genes.iter()).enumerate() {
+            if rng.gen_bool(0.5) {
+                childs[0][i] = *gene_b;
+                childs[1][i] = *gene_a;
+            }
+        }
+        return childs;
+    }
+}
+
+/* --------------------------------------------------------- */
+/* -   DEATH   --------------------------------------------- */
+/* --------------------------------------------------------- */
+
+// cuts a few turns before the turn where the parent crashed: the child keeps
+// the part of the plan that flew and takes the other parent's genes for the end
+pub struct Death {
+    pub backoff: usize,
+    pub genome: Box<dyn Genome>
+}
+
+impl Death {
+    fn cut_point(&self, ship: &Ship, rng: &mut StdRng) -> usize {
+        let death_turn = self.genome.genes_played(&ship.chromosome.genes, ship.path.len());
+        return death_turn - rng.gen_range(0..=self.backoff.min(death_turn));
+    }
+}
+
+impl Crossover for Death {
+    fn cross(&self, a: &Ship, b: &Ship, rng: &mut StdRng) -> [Vec<(i32, i32)>; 2] {
+        let [child_a, _] = cut_at(&a.chromosome.genes, &b.chromosome.genes, self.cut_point(a, rng));
+        let [child_b, _] = cut_at(&b.chromosome.genes, &a.chromosome.genes, self.cut_point(b, rng));
+        return [child_a, child_b];
+    }
+}
+
+/* --------------------------------------------------------- */
+/* -   CUT AND SPLICE   ------------------------------------ */
+/* --------------------------------------------------------- */
+
+// each parent is cut at its own point, the childs grow or shrink
+pub struct CutSplice;
+
+impl Crossover for CutSplice {
+    fn cross(&self, a: &Ship, b: &Ship, rng: &mut StdRng) -> [Vec<(i32, i32)>; 2] {
+        let cut_a = rng.gen_range(0..=a.chromosome.genes.len());
+        let cut_b = rng.gen_range(0..=b.chromosome.genes.len());
+        let mut child_a = a.chromosome.genes[..cut_a].to_vec();
+        child_a.extend_from_slice(&b.chromosome.genes[cut_b..]);
+        let mut child_b = b.chromosome.genes[..cut_b].to_vec();
+        child_b.extend_from_slice(&a.chromosome.genes[cut_a..]);
+        return [child_a, child_b];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsing::parser::{parse_file};
+
+    fn ship(genes: Vec<(i32, i32)>) -> Ship {
+        let level_data = parse_file("levels/01").unwrap();
+        return Ship::from_chromosome(&level_data, Chromosome { genes, fitness: 0.0, breakdown: Default::default() });
+    }
+
+    fn random_ship(size: usize, rng: &mut StdRng) -> Ship {
+        return ship((0..size).map(|_| random_gene(&Delta.bounds(), rng)).collect::<Vec<(i32, i32)>>());
+    }
+
+    fn in_bounds(genes: &[(i32, i32)]) -> bool {
+        return genes.iter().all(|gene| clamp_gene(&Delta.bounds(), *gene) == *gene);
+    }
+
+    #[test]
+    fn point_crossovers_swap_the_genes_in_place() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..100 {
+            let (a, b) = (random_ship(20, &mut rng), random_ship(20, &mut rng));
+            for crossover in [&SinglePoint as &dyn Crossover, &TwoPoint, &Uniform].iter() {
+                let [child_a, child_b] = crossover.cross(&a, &b, &mut rng);
+                assert_eq!((child_a.len(), child_b.len()), (20, 20));
+                for i in 0..20 {
+                    let genes = (a.chromosome.genes[i], b.chromosome.genes[i]);
+                    assert!((child_a[i], child_b[i]) == genes || (child_b[i], child_a[i]) == genes);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn blends_stay_in_bounds_and_keep_the_parent_lengths() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..100 {
+            let (a, b) = (random_ship(20, &mut rng), random_ship(12, &mut rng));
+            for crossover in [&Blend as &dyn Crossover, &Arithmetic { weight: 0.3 }].iter() {
+                let [child_a, child_b] = crossover.cross(&a, &b, &mut rng);
+                assert_eq!((child_a.len(), child_b.len()), (20, 12));
+                assert!(in_bounds(&child_a) && in_bounds(&child_b));
+            }
+        }
+    }
+
+    #[test]
+    fn cuts_swap_the_tails() {
+        let (a, b) = (vec![(1, 0); 4], vec![(2, 0); 6]);
+        assert_eq!(cut_at(&a, &b, 1), [[vec![(1, 0)], vec![(2, 0); 5]].concat(), [vec![(2, 0)], vec![(1, 0); 3]].concat()]);
+        // past the end of the shortest parent, the tail of the longest moves
+        assert_eq!(cut_at(&a, &b, 10), [[vec![(1, 0); 4], vec![(2, 0); 2]].concat(), vec![(2, 0); 4]]);
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..100 {
+            let [child_a, child_b] = CutSplice.cross(&ship(a.clone()), &ship(b.clone()), &mut rng);
+            assert_eq!(child_a.len() + child_b.len(), 10);
+            assert!(child_a.iter().skip_while(|gene| **gene == (1, 0)).all(|gene| *gene == (2, 0)));
+            assert!(child_b.iter().skip_while(|gene| **gene == (2, 0)).all(|gene| *gene == (1, 0)));
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use rand::prelude::*;
+
+use super::game::*;
+use super::genome::*;
+use super::optimizer::*;
+
+/* --------------------------------------------------------- */
+/* -   DIFFERENTIAL EVOLUTION   ---------------------------- */
+/* --------------------------------------------------------- */
+
+// DE/rand/1/bin over the chromosome as a vector of [0, 1] values (see to_unit):
+// each member is challenged by a trial a + f * (b - c) crossed with it value by
+// value with probability `cr`, the trial replaces it when it is at least as good
+pub struct DifferentialEvolution {
+    pub f: f32,
+    pub cr: f32,
+    pub members: Vec<(Vec<f32>, f32)> // values and fitness
+}
+
+impl Optimizer for DifferentialEvolution {
+    fn step(&mut self, game: &mut Game) -> usize {
+        let bounds = game.genome.bounds();
+        // the first step scores the random population of the game
+        if self.members.is_empty() {
+            game.fly_population();
+            game.previous_population = game.ships.clone();
+            game.generation += 1;
+            self.members = game.ships.iter().map(|ship| (to_unit(&ship.chromosome.genes, &bounds), ship.chromosome.fitness)).collect::<Vec<(Vec<f32>, f32)>>();
+            return game.ships.len();
+        }
+
+        let count = self.members.len();
+        let trials = (0..count).map(|i| {
+            let target = &self.members[i].0;
+            if count < 4 {
+                return target.clone();
+            }
+            let mut picked = vec![i];
+            while picked.len() < 4 {
+                let r = game.rng.gen_range(0..count);
+                if !picked.contains(&r) {
+                    picked.push(r);
+                }
+            }
+            let (a, b, c) = (&self.members[picked[1]].0, &self.members[picked[2]].0, &self.members[picked[3]].0);
+            let n = target.len().min(a.len()).min(b.len()).min(c.len());
+            let forced = game.rng.gen_range(0..n.max(1));
+            let mut trial = target.clone();
+            for j in 0..n {
+                if j == forced || game.rng.gen::<f32>() < self.cr {
+                    trial[j] = (a[j] + self.f * (b[j] - c[j])).clamp(0.0, 1.0);
+                }
+            }
+            trial
+        }).collect::<Vec<Vec<f32>>>();
+        game.ships = trials.iter().map(|trial| {
+            Ship::from_chromosome(&game.level_data, Chromosome::from_genes(from_unit(trial, &bounds)))
+        }).collect::<Vec<Ship>>();
+        game.fly_population();
+        game.previous_population = game.ships.clone();
+        game.generation += 1;
+
+        for (i, trial) in trials.into_iter().enumerate() {
+            let fitness = game.ships[i].chromosome.fitness;
+            if fitness >= self.members[i].1 {
+                self.members[i] = (trial, fitness);
+            }
+        }
+        return count;
+    }
+
+    // the members lose the values of the turn flown and are scored again from the new state
+    fn advance(&mut self, game: &mut Game, observed: &Ship) {
+        game.advance(observed);
+        if self.members.is_empty() {
+            return;
+        }
+        let bounds = game.genome.bounds();
+        let plans = self.members.iter().map(|(values, _)| game.shift_genes(&from_unit(values, &bounds))).collect::<Vec<Vec<(i32, i32)>>>();
+        game.ships = plans.into_iter().map(|genes| Ship::from_chromosome(&game.level_data, Chromosome::from_genes(genes))).collect::<Vec<Ship>>();
+        game.fly_population();
+        game.previous_population = game.ships.clone();
+        self.members = game.ships.iter().map(|ship| (to_unit(&ship.chromosome.genes, &bounds), ship.chromosome.fitness)).collect::<Vec<(Vec<f32>, f32)>>();
+    }
+
+    // the elites kept replace the worst members they beat
+    fn resync(&mut self, game: &mut Game, observed: &Ship) -> (usize, usize) {
+        let (kept, dropped) = advance_and_resync(self, game, observed);
+        let bounds = game.genome.bounds();
+        for ship in kept.iter() {
+            let worst = (0..self.members.len()).min_by(|a, b| self.members[*a].1.total_cmp(&self.members[*b].1));
+            match worst {
+                Some(worst) if self.members[worst].1 <= ship.chromosome.fitness => {
+                    self.members[worst] = (to_unit(&ship.chromosome.genes, &bounds), ship.chromosome.fitness);
+                },
+                _ => break
+            }
+        }
+        return (kept.len(), dropped);
+    }
+}
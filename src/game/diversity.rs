@@ -0,0 +1,229 @@
+use std::str::FromStr;
+
+use rand::prelude::*;
+use serde::Deserialize;
+
+use super::game::*;
+use crate::maths::pos::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiversityMetric {
+    Genotype, // how different the genes are
+    Behaviour // how different the flights are (path and crash position)
+}
+
+impl FromStr for DiversityMetric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s {
+            "genotype" => Ok(DiversityMetric::Genotype),
+            "behaviour" => Ok(DiversityMetric::Behaviour),
+            _ => Err(format!("error: unknown diversity metric: {}", s))
+        };
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Niching {
+    None,
+    Sharing, // fitness divided by the number of close ships
+    Crowding, // childs replace their closest parent if they are better
+    Restricted // parents mate with a close partner only
+}
+
+impl FromStr for Niching {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s {
+            "none" => Ok(Niching::None),
+            "sharing" => Ok(Niching::Sharing),
+            "crowding" => Ok(Niching::Crowding),
+            "restricted" => Ok(Niching::Restricted),
+            _ => Err(format!("error: unknown niching: {}", s))
+        };
+    }
+}
+
+const MAP_DIAGONAL: f32 = 7615.773;
+const PATH_SAMPLES: usize = 10;
+
+fn dist(a: &Pos, b: &Pos) -> f32 {
+    return ((a.x - b.x) * (a.x - b.x) + (a.y - b.y) * (a.y - b.y)).sqrt();
+}
+
+// where the flight ended: crash point, or last position when out of map or still flying
+fn final_pos(ship: &Ship) -> &Pos {
+    return if ship.is_dead && !ship.is_out { &ship.crash_pos } else { &ship.pos };
+}
+
+// distance between two ships, between 0 (same) and 1
+pub fn distance(metric: DiversityMetric, a: &Ship, b: &Ship) -> f32 {
+    return match metric {
+        DiversityMetric::Genotype => {
+            let size = a.chromosome.genes.len().min(b.chromosome.genes.len());
+            if size == 0 {
+                return 0.0;
+            }
+            let sum: f32 = a.chromosome.genes.iter().zip(b.chromosome.genes.iter()).map(|(gene_a, gene_b)| {
+                // past 30 degrees or 2 of thrust two genes are as different as can be
+                ((gene_a.0 - gene_b.0).abs() as f32 / 30.0).min(1.0) + ((gene_a.1 - gene_b.1).abs() as f32 / 2.0).min(1.0)
+            }).sum();
+            sum / (2 * size) as f32
+        },
+        DiversityMetric::Behaviour => {
+            let crash = dist(final_pos(a), final_pos(b)) / MAP_DIAGONAL;
+            if a.path.is_empty() || b.path.is_empty() {
+                return crash;
+            }
+            // both paths are sampled at the same turns, the shorter one stays on its last point
+            let turns = a.path.len().max(b.path.len());
+            let mut path = 0.0;
+            for k in 0..PATH_SAMPLES {
+                let turn = k * (turns - 1) / (PATH_SAMPLES - 1);
+                path += dist(&a.path[turn.min(a.path.len() - 1)], &b.path[turn.min(b.path.len() - 1)]) / MAP_DIAGONAL;
+            }
+            0.5 * crash + 0.5 * path / PATH_SAMPLES as f32
+        }
+    };
+}
+
+/* --------------------------------------------------------- */
+/* -   FITNESS SHARING   ----------------------------------- */
+/* --------------------------------------------------------- */
+
+// the fitness of a ship is shared with every ship closer than `radius`
+pub fn shared_fitness(ships: &[Ship], metric: DiversityMetric, radius: f32, alpha: f32) -> Vec<f32> {
+    let radius = radius.max(f32::EPSILON);
+    return ships.iter().map(|ship| {
+        let niche_count: f32 = ships.iter().map(|other| {
+            let d = distance(metric, ship, other);
+            if d < radius { 1.0 - (d / radius).powf(alpha) } else { 0.0 }
+        }).sum();
+        ship.chromosome.fitness / niche_count.max(1.0)
+    }).collect::<Vec<f32>>();
+}
+
+/* --------------------------------------------------------- */
+/* -   RESTRICTED MATING   --------------------------------- */
+/* --------------------------------------------------------- */
+
+// picks among `candidates` a partner closer than `radius` to `ships[partner]`, and never
+// an identical one, falls back on the first different candidate
+pub fn pick_mate(ships: &[Ship], partner: usize, candidates: &[usize], metric: DiversityMetric, radius: f32) -> Option<usize> {
+    let mut fallback = None;
+    for candidate in candidates.iter() {
+        let d = distance(metric, &ships[partner], &ships[*candidate]);
+        if d > 0.0 && d <= radius {
+            return Some(*candidate);
+        }
+        if d > 0.0 && fallback.is_none() {
+            fallback = Some(*candidate);
+        }
+    }
+    return fallback;
+}
+
+/* --------------------------------------------------------- */
+/* -   DETERMINISTIC CROWDING   ---------------------------- */
+/* --------------------------------------------------------- */
+
+// childs `2k` and `2k + 1` were bred from `parents[2k]` and `parents[2k + 1]`,
+// each child competes with the parent it is the closest to
+pub fn crowding_replacement(parents: &[Ship], childs: &[Ship], metric: DiversityMetric) -> Vec<Ship> {
+    let mut survivors = vec![];
+    let better = |parent: &Ship, child: &Ship| if child.chromosome.fitness >= parent.chromosome.fitness { child.clone() } else { parent.clone() };
+    let mut k = 0;
+    while k < childs.len() {
+        if k + 1 < childs.len() {
+            let (p1, p2, c1, c2) = (&parents[k], &parents[k + 1], &childs[k], &childs[k + 1]);
+            if distance(metric, p1, c1) + distance(metric, p2, c2) <= distance(metric, p1, c2) + distance(metric, p2, c1) {
+                survivors.push(better(p1, c1));
+                survivors.push(better(p2, c2));
+            } else {
+                survivors.push(better(p1, c2));
+                survivors.push(better(p2, c1));
+            }
+        } else {
+            survivors.push(better(&parents[k], &childs[k]));
+        }
+        k += 2;
+    }
+    return survivors;
+}
+
+pub fn crowding_generation(game: &mut Game) {
+    let childs = std::mem::take(&mut game.ships);
+    let parents = std::mem::take(&mut game.archive);
+    // the first generation has no parents yet
+    let survivors = match parents.len() == childs.len() {
+        true => crowding_replacement(&parents, &childs, game.settings.diversity_metric),
+        false => childs
+    };
+    game.previous_population = survivors.clone();
+
+    let mut parents = survivors;
+    parents.shuffle(&mut game.rng);
+    let count = parents.len();
+    let pairs = (0..count).step_by(2).map(|k| (k, (k + 1) % count)).collect::<Vec<(usize, usize)>>();
+    game.update_mutation_rate(&parents);
+    let mut childs = game.breed(&parents, &pairs);
+    childs.truncate(count);
+    game.ships = childs;
+    game.archive = parents;
+    game.generation += 1;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsing::parser::{parse_file};
+
+    fn ship(genes: Vec<(i32, i32)>, fitness: f32) -> Ship {
+        let level_data = parse_file("levels/01").unwrap();
+        return Ship::from_chromosome(&level_data, Chromosome { genes, fitness, breakdown: Default::default() });
+    }
+
+    #[test]
+    fn distances_go_from_0_to_1() {
+        let a = ship(vec![(0, 0); 4], 0.0);
+        let b = ship(vec![(15, 1), (-15, -1), (0, 0), (0, 0)], 0.0);
+        let c = ship(vec![(-30, 2); 4], 0.0);
+        assert_eq!(distance(DiversityMetric::Genotype, &a, &a), 0.0);
+        assert_eq!(distance(DiversityMetric::Genotype, &a, &b), distance(DiversityMetric::Genotype, &b, &a));
+        assert!((distance(DiversityMetric::Genotype, &a, &b) - 0.25).abs() < 1e-6);
+        assert_eq!(distance(DiversityMetric::Genotype, &a, &c), 1.0);
+        let mut far = a.clone();
+        far.pos = Pos::from(a.pos.x + MAP_DIAGONAL, a.pos.y);
+        far.path = vec![a.pos.clone(), far.pos.clone()];
+        let mut near = a.clone();
+        near.path = vec![a.pos.clone(), a.pos.clone()];
+        assert_eq!(distance(DiversityMetric::Behaviour, &near, &near), 0.0);
+        let behaviour = distance(DiversityMetric::Behaviour, &near, &far);
+        assert!(behaviour > 0.5 && behaviour <= 1.0, "{}", behaviour);
+    }
+
+    #[test]
+    fn sharing_divides_the_fitness_within_the_radius() {
+        let ships = vec![ship(vec![(0, 0); 4], 90.0), ship(vec![(0, 0); 4], 90.0), ship(vec![(-30, 2); 4], 90.0)];
+        let shared = shared_fitness(&ships, DiversityMetric::Genotype, 0.5, 1.0);
+        // the two identical ships count each other, the third one is alone in its niche
+        assert_eq!(shared, vec![45.0, 45.0, 90.0]);
+    }
+
+    #[test]
+    fn crowding_childs_compete_with_their_closest_parent() {
+        let parents = vec![ship(vec![(0, 0); 4], 50.0), ship(vec![(-30, 2); 4], 50.0)];
+        // the first child looks like the second parent and the second like the first
+        let childs = vec![ship(vec![(-28, 2); 4], 60.0), ship(vec![(2, 0); 4], 40.0)];
+        let survivors = crowding_replacement(&parents, &childs, DiversityMetric::Genotype);
+        assert_eq!(survivors[0].chromosome, parents[0].chromosome);
+        assert_eq!(survivors[1].chromosome, childs[0].chromosome);
+        // a child without a partner competes with its own parent
+        let survivors = crowding_replacement(&parents[..1], &childs[..1], DiversityMetric::Genotype);
+        assert_eq!(survivors[0].chromosome, childs[0].chromosome);
+    }
+}
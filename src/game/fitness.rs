@@ -0,0 +1,186 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+use super::game::*;
+use crate::maths::pos::{Pos};
+use crate::maths::utils::*;
+
+pub trait Fitness: Send + Sync {
+    fn score(&self, game: &Game, ship: &Ship) -> FitnessBreakdown;
+}
+
+// every term of the score, the fitness is their sum
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FitnessBreakdown {
+    pub band: f32,
+    pub distance: f32,
+    pub speed: f32,
+    pub h_speed: f32,
+    pub v_speed: f32,
+    pub angle: f32,
+    pub fuel: f32
+}
+
+impl FitnessBreakdown {
+    pub fn total(&self) -> f32 {
+        return self.band + self.distance + self.speed + self.h_speed + self.v_speed + self.angle + self.fuel;
+    }
+}
+
+impl fmt::Display for FitnessBreakdown {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "{:.1} = band {:.0} | dist {:.1} | speed {:.1} | h_speed {:.1} | v_speed {:.1} | angle {:.1} | fuel {:.1}",
+            self.total(), self.band, self.distance, self.speed, self.h_speed, self.v_speed, self.angle, self.fuel);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct FitnessWeights {
+    pub out_band: f32,
+    pub crash_band: f32,
+    pub airborne_band: f32,
+    pub landing_band: f32,
+    pub solution_band: f32,
+    pub distance: f32,
+    pub crash_speed_threshold: f32,
+    pub crash_speed: f32,
+    pub h_speed: f32,
+    pub v_speed: f32,
+    pub angle: f32,
+    pub fuel: f32
+}
+
+impl Default for FitnessWeights {
+    fn default() -> Self {
+        return Self {
+            out_band: 1.0,
+            crash_band: 1.0,
+            airborne_band: 1.0,
+            landing_band: 100.0,
+            solution_band: 200.0,
+            distance: 99.0,
+            crash_speed_threshold: 100.0,
+            crash_speed: 0.1,
+            h_speed: 50.0,
+            v_speed: 50.0,
+            angle: 0.0,
+            fuel: 100.0
+        }
+    }
+}
+
+// how far from the landing zone a crash is
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DistanceMetric {
+    Surface, // along the ground
+    FreeSpace // shortest path through the air
+}
+
+impl FromStr for DistanceMetric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s {
+            "surface" => Ok(DistanceMetric::Surface),
+            "free_space" => Ok(DistanceMetric::FreeSpace),
+            _ => Err(format!("error: unknown distance metric: {}", s))
+        };
+    }
+}
+
+/* --------------------------------------------------------- */
+/* -   BANDED FITNESS   ------------------------------------ */
+/* --------------------------------------------------------- */
+
+// out of map < crash or still flying < bad landing < landing, each outcome starts at its own band
+pub struct BandedFitness {
+    pub weights: FitnessWeights,
+    pub metric: DistanceMetric
+}
+
+impl BandedFitness {
+    fn distance_score(&self, game: &Game, ship: &Ship) -> f32 {
+        // a ship still flying at the end of its chromosome did not touch the ground,
+        // it is measured with the same metric from where it stopped
+        let dist = match self.metric {
+            DistanceMetric::Surface if ship.is_dead => game.calc_min_dist(&ship.crash_pos, ship.crash_zone_index),
+            DistanceMetric::Surface => match segment_below(&game.map, &ship.pos) {
+                // straight down to the ground then along it
+                Some((index, y)) => (ship.pos.y - y) + game.calc_min_dist(&Pos::from(ship.pos.x, y), index),
+                None => f32::INFINITY
+            },
+            DistanceMetric::FreeSpace if ship.is_dead => game.visibility.distance(&ship.crash_pos, Some(ship.crash_zone_index)),
+            DistanceMetric::FreeSpace => game.visibility.distance(&ship.pos, None)
+        };
+        let max_dist = match self.metric {
+            DistanceMetric::Surface => game.surface_length as f32,
+            DistanceMetric::FreeSpace => game.visibility.max_dist
+        };
+        return match dist.is_finite() {
+            true => scale(dist, 0.0, max_dist.max(1.0), self.weights.distance, 0.0).max(0.0),
+            false => 0.0
+        };
+    }
+}
+
+impl Fitness for BandedFitness {
+    fn score(&self, game: &Game, ship: &Ship) -> FitnessBreakdown {
+        let w = &self.weights;
+        let mut breakdown = FitnessBreakdown::default();
+        if ship.is_out {
+            breakdown.band = w.out_band;
+        } else if !ship.is_dead || ship.crash_zone_index != game.landing_zone_index {
+            let speed = ((ship.h_speed * ship.h_speed) + (ship.v_speed * ship.v_speed)).sqrt(); // 0 to 707.106781187
+            breakdown.band = if ship.is_dead { w.crash_band } else { w.airborne_band };
+            breakdown.distance = self.distance_score(game, ship);
+            if speed > w.crash_speed_threshold {
+                breakdown.speed = 0.0 - w.crash_speed * speed;
+            }
+        } else if !ship.is_solution {
+            breakdown.band = w.landing_band;
+            breakdown.h_speed = w.h_speed;
+            if ship.h_speed.abs() > 20.0 {
+                breakdown.h_speed = scale(ship.h_speed.abs(), 500.0, 20.0, 0.0, w.h_speed);
+            }
+            breakdown.v_speed = w.v_speed;
+            if ship.v_speed < -40.0 {
+                breakdown.v_speed = scale(ship.v_speed, -500.0, -40.0, 0.0, w.v_speed);
+            }
+            breakdown.angle = 0.0 - scale(ship.angle.abs(), 0.0, 90.0, 0.0, w.angle);
+        } else {
+            breakdown.band = w.solution_band;
+            breakdown.fuel = scale(ship.fuel, 0.0, game.level_data.fuel, 0.0, w.fuel);
+        }
+        return breakdown;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsing::parser::{parse_file};
+    use crate::parsing::settings::{Settings};
+
+    #[test]
+    fn bands_rank_the_outcomes() {
+        let level_data = parse_file("levels/01").unwrap();
+        let game = Game::setup(&level_data, &Settings::default());
+        let ship = Ship::from_chromosome(&level_data, Chromosome { genes: vec![], fitness: 0.0, breakdown: FitnessBreakdown::default() });
+        // the right slope of the level, past the landing zone
+        let ground = Pos::from(6000.0, 150.0 + 650.0 * 500.0 / 1499.0);
+        let out = Ship { is_out: true, is_dead: true, pos: Pos::from(7100.0, 2000.0), ..ship.clone() };
+        let dead = Ship { is_dead: true, pos: ground.clone(), crash_pos: ground.clone(), crash_zone_index: 5, v_speed: -150.0, ..ship.clone() };
+        let airborne = Ship { pos: Pos::from(ground.x, ground.y + 50.0), v_speed: -30.0, ..ship.clone() };
+        let landed = Ship { is_dead: true, crash_zone_index: game.landing_zone_index, h_speed: 25.0, v_speed: -60.0, ..ship.clone() };
+        let solution = Ship { is_solution: true, fuel: 10.0, ..landed.clone() };
+        for metric in [DistanceMetric::Surface, DistanceMetric::FreeSpace].iter() {
+            let fitness = BandedFitness { weights: FitnessWeights::default(), metric: *metric };
+            let scores = [&out, &dead, &airborne, &landed, &solution].iter().map(|ship| fitness.score(&game, ship).total()).collect::<Vec<f32>>();
+            assert!(scores.windows(2).all(|pair| pair[0] < pair[1]), "{:?}: {:?}", metric, scores);
+        }
+    }
+}
@@ -2,13 +2,25 @@ extern crate rand;
 
 use crate::maths::pos::*;
 use crate::maths::utils::*;
+use crate::maths::visibility::*;
 use crate::parsing::parser::{LevelData};
+use crate::parsing::settings::{Settings};
+use super::selection::*;
+use super::crossover::*;
+use super::mutation::*;
+use super::fitness::*;
+use super::nsga;
+use super::diversity::*;
+use super::restart::*;
+use super::local_search::*;
+use super::repair::*;
+use super::genome::*;
+use super::pilot::*;
+use super::gradient::*;
 use rand::prelude::*;
-
-pub const POPULATION_COUNT: usize = 100; // default: 100
-pub const CHROMOSOME_SIZE: usize = 180; // default: 200
-pub const ELITE_PERCENTAGE: f32 = 0.12; // default: 0.12
-pub const MUTATION_RATE: f32 = 0.01; // default: 0.01
+use rayon::prelude::*;
+use serde::Deserialize;
+use std::str::FromStr;
 
 /* --------------------------------------------------------- */
 /* -   CHROMOSOME   ---------------------------------------- */
@@ -16,21 +28,26 @@ pub const MUTATION_RATE: f32 = 0.01; // default: 0.01
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Chromosome {
-    pub genes: [(i32, i32); CHROMOSOME_SIZE], // angle, thrust
+    pub genes: Vec<(i32, i32)>, // angle, thrust, read by the genome
     pub fitness: f32,
-    pub prob: f32
+    pub breakdown: FitnessBreakdown
 }
 
 impl Chromosome {
-    fn new(rng: &mut ThreadRng) -> Self {
-        let mut genes = [(0, 0); CHROMOSOME_SIZE];
-        for i in 0..CHROMOSOME_SIZE {
-            genes[i] = (rng.gen_range(-15..16), rng.gen_range(-1..2));
-        }
+    fn new(size: usize, genome: &dyn Genome, rng: &mut StdRng) -> Self {
+        let bounds = genome.bounds();
+        return Self {
+            genes: (0..size).map(|_| random_gene(&bounds, rng)).collect::<Vec<(i32, i32)>>(),
+            fitness: 0.0,
+            breakdown: FitnessBreakdown::default()
+        };
+    }
+
+    pub fn from_genes(genes: Vec<(i32, i32)>) -> Self {
         return Self {
-            genes: genes,
+            genes,
             fitness: 0.0,
-            prob: 0.0
+            breakdown: FitnessBreakdown::default()
         };
     }
 }
@@ -58,9 +75,13 @@ pub struct Ship {
 }
 
 impl Ship {
-    fn new(level_data: &LevelData, rng: &mut ThreadRng) -> Self {
+    pub fn new(level_data: &LevelData, chromosome_size: usize, genome: &dyn Genome, rng: &mut StdRng) -> Self {
+        return Ship::from_chromosome(level_data, Chromosome::new(chromosome_size, genome, rng));
+    }
+
+    pub fn from_chromosome(level_data: &LevelData, chromosome: Chromosome) -> Self {
         return Self {
-            chromosome: Chromosome::new(rng),
+            chromosome,
             pos: level_data.pos.clone(),
             angle: level_data.angle,
             power: level_data.power,
@@ -82,14 +103,14 @@ impl Ship {
     }
 
     pub fn simulate(&mut self, angle: f32, power: f32, gravity: f32) {
-        let clamped_angle = angle.max(-15.0).min(15.0);
+        let clamped_angle = angle.clamp(-15.0, 15.0);
         self.angle += clamped_angle;
-        self.angle = self.angle.max(-90.0).min(90.0);
+        self.angle = self.angle.clamp(-90.0, 90.0);
 
         if self.fuel > 0.0 {
-            let clamped_power = power.max(-1.0).min(1.0);
+            let clamped_power = power.clamp(-1.0, 1.0);
             self.power += clamped_power;
-            self.power = self.power.max(0.0).min(4.0);
+            self.power = self.power.clamp(0.0, 4.0);
             self.fuel -= self.power;
         } else {
             self.power = 0.0;
@@ -102,41 +123,37 @@ impl Ship {
         self.h_speed += h_acc;
         self.path.push(self.pos.clone());
     }
+}
 
-    fn crossover(&self, partner: Ship, level_data: &LevelData, rng: &mut ThreadRng) -> [Ship; 2] {
-        let mut childs: [Ship; 2] = [Ship::new(level_data, rng), Ship::new(level_data, rng)];
+/* --------------------------------------------------------- */
+/* -   GAME   ---------------------------------------------- */
+/* --------------------------------------------------------- */
 
-        for i in 0..CHROMOSOME_SIZE {
-            let gene_a_angle = self.chromosome.genes[i].0 as f32;
-            let gene_a_power = self.chromosome.genes[i].1 as f32;
-            let gene_b_angle = partner.chromosome.genes[i].0 as f32;
-            let gene_b_power = partner.chromosome.genes[i].1 as f32;
-            let r: f32 = rng.gen();
-            childs[0].chromosome.genes[i as usize] = ((r * gene_a_angle + (1.0 - r) * gene_b_angle).round() as i32, (r * gene_a_power + (1.0 - r) * gene_b_power).round()  as i32);
-            childs[1].chromosome.genes[i as usize] = (((1.0 - r) * gene_a_angle + r * gene_b_angle).round() as i32, ((1.0 - r) * gene_a_power + r * gene_b_power).round()  as i32);
-        }
-        return childs;
-    }
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    Single, // scalar fitness with elitism
+    Nsga2 // multi-objective, keeps a pareto front of landings
+}
 
-    fn mutate(&mut self, mutation_rate: f32, rng: &mut ThreadRng) {
-        for i in 0..self.chromosome.genes.len() {
-            if rng.gen_bool(mutation_rate as f64) {
-                self.chromosome.genes[i].0 = rng.gen_range(-15..16);
+impl FromStr for Mode {
+    type Err = String;
 
-            }
-            if rng.gen_bool(mutation_rate as f64)  {
-                self.chromosome.genes[i].1 = rng.gen_range(-1..2);
-            }
-        }
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s {
+            "single" => Ok(Mode::Single),
+            "nsga2" => Ok(Mode::Nsga2),
+            _ => Err(format!("error: unknown mode: {}", s))
+        };
     }
 }
 
-/* --------------------------------------------------------- */
-/* -   GAME   ---------------------------------------------- */
-/* --------------------------------------------------------- */
-
 pub struct Game {
     pub level_data: LevelData,
+    pub settings: Settings,
+    pub island: usize,
+    pub seed: u64,
+    pub verbose: bool,
     pub gravity: f32,
     pub map: Vec<Pos>,
     pub landing_zone_xmin: f32,
@@ -144,20 +161,33 @@ pub struct Game {
     pub landing_zone_y: f32,
     pub landing_zone_index: usize,
     pub surface_length: i32,
+    pub visibility: VisibilityGraph,
     pub ships: Vec<Ship>,
     pub turn: usize,
     pub paused: bool,
     pub next_turn: bool,
     pub mutation_rate: f32,
     pub generation: i32,
+    pub best_fitness: f32,
+    pub stagnant_generations: usize,
     pub search_ended: bool,
     pub best_ship: Option<Ship>,
     pub previous_population: Vec<Ship>,
-    pub rng: ThreadRng
+    pub archive: Vec<Ship>,
+    pub hall_of_fame: Vec<Ship>,
+    pub restarts: usize,
+    pub restart_log: Option<String>,
+    pub hypermutation_left: usize,
+    pub selection: Box<dyn Selection>,
+    pub crossover: Box<dyn Crossover>,
+    pub mutation: Box<dyn Mutation>,
+    pub fitness: Box<dyn Fitness>,
+    pub genome: Box<dyn Genome>,
+    pub rng: StdRng
 }
 
 impl Game {
-    pub fn setup(level_data: &LevelData) -> Self {
+    pub fn setup(level_data: &LevelData, settings: &Settings) -> Self {
         let mut landing_zone_xmin = 0.0;
         let mut landing_zone_xmax = 0.0;
         let mut landing_zone_y = 0.0;
@@ -174,9 +204,15 @@ impl Game {
             let y_length = level_data.map[i].y - level_data.map[i + 1].y;
             surface_length += ((x_length * x_length) + (y_length * y_length)).sqrt() as i32;
         }
-        let mut rng = rand::thread_rng();
-        return Self {
+        let seed = settings.seed.unwrap_or_else(|| rand::thread_rng().gen());
+        let mut rng = StdRng::seed_from_u64(seed);
+        let genome = settings.encoding.build(settings);
+        let mut game = Self {
             level_data: level_data.clone(),
+            settings: settings.clone(),
+            island: 0,
+            seed,
+            verbose: true,
             gravity: 3.711,
             map: level_data.map.clone(),
             landing_zone_xmin,
@@ -184,74 +220,350 @@ impl Game {
             landing_zone_y,
             landing_zone_index,
             surface_length,
-            ships: (0..POPULATION_COUNT).map(|_| Ship::new(level_data, &mut rng)).collect::<Vec<Ship>>(),
+            visibility: VisibilityGraph::new(&level_data.map, landing_zone_index),
+            ships: (0..settings.population_count).map(|_| Ship::new(level_data, settings.chromosome_size, &*genome, &mut rng)).collect::<Vec<Ship>>(),
             turn: 0,
             paused: true,
             next_turn: false,
-            mutation_rate: MUTATION_RATE,
+            mutation_rate: settings.mutation_rate,
             generation: 0,
+            best_fitness: 0.0,
+            stagnant_generations: 0,
             search_ended: false,
             best_ship: None,
             previous_population: vec![],
-            rng: rng
+            archive: vec![],
+            hall_of_fame: vec![],
+            restarts: 0,
+            restart_log: None,
+            hypermutation_left: 0,
+            selection: settings.selection.build(settings),
+            crossover: settings.crossover.build(settings),
+            mutation: settings.mutation.build(settings),
+            fitness: Box::new(BandedFitness { weights: settings.fitness.clone(), metric: settings.distance_metric }),
+            genome,
+            rng
+        };
+        // validate() rejects the pilot seeds with the segments encoding, the only
+        // one that can't fly the pilot plan turn by turn
+        if settings.pilot_seeds > 0 {
+            if let Some(genes) = pilot_genes(&game) {
+                seed_with_pilot(&mut game, &genes);
+            }
         }
+        return game;
     }
     
-    fn pick_partner(&mut self) -> Ship {
-        let mut rng = rand::thread_rng();
-        let mut selected: Option<Ship> = None;
-        let r: f32 = rng.gen();
-        if r < self.ships[0].chromosome.prob {
-            return self.ships[0].clone();
-        }
-        for i in 1..POPULATION_COUNT {
-            if self.ships[i - 1].chromosome.prob < r && r <= self.ships[i].chromosome.prob {
-                selected = Some(self.ships[i].clone());
+    // plays one turn and checks if the ship touched the ground or left the map
+    pub fn step_ship(&self, ship: &mut Ship, angle: f32, power: f32) {
+        let prev_pos = ship.pos.clone();
+        ship.simulate(angle, power, self.gravity);
+        for index in 0..(self.map.len() - 1) {
+            let a = &self.map[index];
+            let b = &self.map[index + 1];
+
+            if do_intersect(a, b, &prev_pos, &ship.pos) {
+                ship.crash_pos = find_intersection_point(a, b, &prev_pos, &ship.pos);
+                ship.crash_zone_index = index;
+                if ship.crash_zone_index == self.landing_zone_index && ship.angle == 0.0 && ship.v_speed >= -40.0 && ship.h_speed.abs() <= 20.0 {
+                    ship.is_solution = true;
+                }
+                ship.is_dead = true;
                 break;
             }
         }
-        return selected.unwrap();
+        if !ship.is_dead && ship.is_out_of_map() { // FIXME, it mean no intersection and out of map, maybe find better way to do that
+            ship.is_out = true;
+            ship.is_dead = true;
+        }
+    }
+
+    // plays the whole chromosome, until the ship dies
+    pub fn fly(&self, ship: &mut Ship) {
+        let targets = self.genome.decode(&ship.chromosome.genes, self.level_data.angle as i32, self.level_data.power as i32);
+        let mut turn = ship.path.len();
+        while !ship.is_dead && turn < targets.len() {
+            let (angle, power) = targets[turn];
+            self.step_ship(ship, angle as f32 - ship.angle, power as f32 - ship.power);
+            turn += 1;
+        }
+    }
+
+    // flies the ships that did not fly yet, repairs the near misses and scores them all
+    pub fn fly_population(&mut self) {
+        let mut ships = std::mem::take(&mut self.ships);
+        let game: &Game = self;
+        ships.par_iter_mut().filter(|ship| !ship.is_dead).for_each(|ship| game.fly(ship));
+        self.ships = ships;
+        let (tried, repaired) = if self.settings.repair_turns > 0 { repair_near_misses(self) } else { (0, 0) };
+        self.evaluate();
+        if self.verbose && tried > 0 {
+            eprintln!("    repair: {} of {} near misses land", repaired, tried);
+        }
+    }
+
+    pub fn run_generation(&mut self) {
+        check_stagnation(self);
+        self.fly_population();
+        match self.settings.mode {
+            Mode::Single if self.settings.niching == Niching::Crowding => crowding_generation(self),
+            Mode::Single => {
+                let mut elites = self.get_elites();
+                if self.settings.local_search != LocalSearch::None {
+                    let (count, gained) = improve_elites(self, &mut elites);
+                    if self.verbose && count > 0 {
+                        eprintln!("    local search: +{} fuel on {} landings", gained, count);
+                    }
+                }
+                self.previous_population = self.ships.clone();
+                self.generate();
+                for (ship, elite) in self.ships.iter_mut().zip(elites) {
+                    *ship = elite;
+                    ship.is_elite = true;
+                }
+            },
+            Mode::Nsga2 => nsga::next_generation(self)
+        }
+    }
+
+    // migrants take the place of the last childs, from the end of the population
+    // backward, each in its own slot, elites are kept
+    pub fn receive_migrants(&mut self, migrants: &[Ship]) {
+        let slots = (0..self.ships.len()).rev().filter(|index| !self.ships[*index].is_elite).collect::<Vec<usize>>();
+        for (index, migrant) in slots.into_iter().zip(migrants.iter()) {
+            self.ships[index] = migrant.clone();
+            self.ships[index].is_elite = false;
+        }
+    }
+
+    pub fn end_search(&mut self) {
+        self.search_ended = true;
+        self.paused = true;
+        self.turn = 0;
+        if self.best_ship.is_none() {
+            self.best_ship = self.previous_population.iter().max_by(|a, b| a.chromosome.fitness.partial_cmp(&b.chromosome.fitness).unwrap()).cloned();
+        }
+    }
+
+    // local search on the best landing once the time limit is reached
+    pub fn polish_best(&mut self) {
+        let steps = self.settings.local_search_end_steps;
+        if self.settings.local_search == LocalSearch::None || steps == 0 {
+            return;
+        }
+        let mut rng = StdRng::seed_from_u64(self.rng.gen());
+        if let Some(best) = self.best_ship.take() {
+            let polished = self.settings.local_search.improve(self, &best, steps, &mut rng);
+            eprintln!("local search: +{} fuel ({} -> {})", polished.fuel - best.fuel, best.fuel, polished.fuel);
+            self.best_ship = Some(polished);
+        }
+    }
+
+    // gradient descent on the fuel of the best landing once the time limit is reached
+    pub fn refine_best(&mut self) {
+        if self.settings.gradient_steps == 0 {
+            return;
+        }
+        if let Some(best) = self.best_ship.take() {
+            match refine(self, &best, self.settings.gradient_steps, self.settings.gradient_rate as f64) {
+                Some(refined) => {
+                    eprintln!("gradient: +{} fuel ({} -> {})", refined.fuel - best.fuel, best.fuel, refined.fuel);
+                    self.best_ship = Some(refined);
+                },
+                None => {
+                    eprintln!("gradient: no better landing");
+                    self.best_ship = Some(best);
+                }
+            }
+        }
+    }
+
+    // flies and scores a chromosome from the start
+    pub fn evaluate_genes(&self, genes: Vec<(i32, i32)>) -> Ship {
+        let mut ship = Ship::from_chromosome(&self.level_data, Chromosome::from_genes(genes));
+        self.fly(&mut ship);
+        let breakdown = self.fitness.score(self, &ship);
+        ship.chromosome.fitness = breakdown.total();
+        ship.chromosome.breakdown = breakdown;
+        return ship;
     }
 
+    // flies a given plan instead of searching one
+    pub fn replay(&mut self, genes: Vec<(i32, i32)>) {
+        let ship = self.evaluate_genes(genes);
+        eprintln!("replay: {}", ship.chromosome.breakdown);
+        self.best_ship = Some(ship);
+        self.end_search();
+    }
+
+    // the genes of a plan once its first turn is flown, random genes keep its length
+    pub fn shift_genes(&mut self, genes: &[(i32, i32)]) -> Vec<(i32, i32)> {
+        let bounds = self.genome.bounds();
+        let mut shifted = self.genome.shift(genes);
+        while shifted.len() < genes.len() {
+            shifted.push(random_gene(&bounds, &mut self.rng));
+        }
+        return shifted;
+    }
+
+    // `ships` without the turn flown, flown again from the current state
+    fn fly_shifted(&mut self, ships: &[Ship]) -> Vec<Ship> {
+        let plans = ships.iter().map(|ship| self.shift_genes(&ship.chromosome.genes)).collect::<Vec<Vec<(i32, i32)>>>();
+        let game: &Game = self;
+        return plans.into_par_iter().map(|genes| game.evaluate_genes(genes)).collect::<Vec<Ship>>();
+    }
+
+    // the ship flew the first turn of the plans: the search goes on from the
+    // `observed` state, every ship loses its first turn and flies again, the
+    // best landing is flown again from there and dropped if it no longer lands,
+    // the ships kept aside (archive, hall of fame) are flown again too
+    pub fn advance(&mut self, observed: &Ship) {
+        self.level_data.pos = observed.pos.clone();
+        self.level_data.angle = observed.angle;
+        self.level_data.power = observed.power;
+        self.level_data.h_speed = observed.h_speed;
+        self.level_data.v_speed = observed.v_speed;
+        self.level_data.fuel = observed.fuel;
+
+        let ships = std::mem::take(&mut self.ships);
+        self.ships = ships.iter().map(|ship| {
+            let genes = self.shift_genes(&ship.chromosome.genes);
+            Ship::from_chromosome(&self.level_data, Chromosome::from_genes(genes))
+        }).collect::<Vec<Ship>>();
+        self.previous_population = vec![];
+        // the parents kept by nsga2 and crowding and the hall of fame compete with
+        // the next ships on their fitness, they are flown again from the new state
+        let archive = std::mem::take(&mut self.archive);
+        self.archive = self.fly_shifted(&archive);
+        let hall_of_fame = std::mem::take(&mut self.hall_of_fame);
+        self.hall_of_fame = self.fly_shifted(&hall_of_fame);
+        self.best_fitness = 0.0;
+        if let Some(best) = self.best_ship.take() {
+            let genes = self.shift_genes(&best.chromosome.genes);
+            let best = self.evaluate_genes(genes);
+            if best.is_solution {
+                self.best_fitness = best.chromosome.fitness;
+                self.best_ship = Some(best);
+            }
+        }
+    }
+
+    // the best ships of the last step, as many as the elites of a generation
+    pub fn last_elites(&self) -> Vec<Ship> {
+        let count = ((self.settings.population_count as f32 * self.settings.elite_percentage) as usize).max(1);
+        let mut ships = self.previous_population.clone();
+        ships.sort_by(|a, b| b.chromosome.fitness.total_cmp(&a.chromosome.fitness));
+        ships.truncate(count);
+        return ships;
+    }
+
+    // the observed state drifted from the predicted one and the search advanced
+    // from it: the `elites` of the last step are flown again from there, an elite
+    // that landed and no longer does, or that crashes sooner, is dropped. The
+    // others take the place of the first ships, their best landing can become the
+    // best ship. Returns the elites kept, flown from the new state
+    pub fn resync(&mut self, elites: &[Ship]) -> Vec<Ship> {
+        let flown = self.fly_shifted(elites);
+        let kept = elites.iter().zip(flown)
+            .filter(|(elite, ship)| if elite.is_solution { ship.is_solution } else { ship.is_solution || ship.path.len() + 1 >= elite.path.len() })
+            .map(|(_, ship)| ship)
+            .collect::<Vec<Ship>>();
+        for ship in kept.iter() {
+            if ship.is_solution && self.best_ship.as_ref().is_none_or(|best| ship.chromosome.fitness > best.chromosome.fitness) {
+                self.best_ship = Some(ship.clone());
+            }
+            self.best_fitness = self.best_fitness.max(ship.chromosome.fitness);
+        }
+        let count = kept.len().min(self.ships.len());
+        for (i, ship) in kept.iter().take(count).enumerate() {
+            self.ships[i] = ship.clone();
+        }
+        return kept;
+    }
+
+    // landings of the last generation that no other landing beats on every objective
+    pub fn pareto_front(&self) -> Vec<Ship> {
+        return nsga::pareto_front(&self.previous_population, &self.settings.objectives);
+    }
+
+    // with niching, a ship identical to a better elite is not kept twice
     pub fn get_elites(&self) -> Vec<Ship> {
         let mut ships = self.ships.clone();
-        let mut elites = vec![];
+        let mut elites: Vec<Ship> = vec![];
+        let elite_count = (self.settings.population_count as f32 * self.settings.elite_percentage) as usize;
         ships.sort_by(|a, b| b.chromosome.fitness.partial_cmp(&a.chromosome.fitness).unwrap());
-        for i in 0..(POPULATION_COUNT as f32 * ELITE_PERCENTAGE) as usize {
-            elites.push(ships[i].clone());
+        for ship in ships.into_iter() {
+            if elites.len() >= elite_count {
+                break;
+            }
+            if self.settings.niching == Niching::None || elites.iter().all(|elite| distance(self.settings.diversity_metric, elite, &ship) > 0.0) {
+                elites.push(ship);
+            }
         }
         return elites;
     }
-    
-    pub fn generate(&mut self) {
-        let mut new_ships: Vec<Ship> = vec![];
-        let mut fitness_sum: f32 = 0.0;
-        for i in 0..POPULATION_COUNT {
-            fitness_sum += self.ships[i].chromosome.fitness;
-        }
-        let mut prob_sum = 0.0;
-        for i in 0..POPULATION_COUNT {
-            self.ships[i].chromosome.prob = prob_sum + self.ships[i].chromosome.fitness / fitness_sum;
-            prob_sum += self.ships[i].chromosome.prob;
+
+    pub fn update_mutation_rate(&mut self, ships: &[Ship]) {
+        if self.hypermutation_left > 0 {
+            self.mutation_rate = self.settings.hypermutation_rate;
+            return;
         }
-    
-        for _ in (0..POPULATION_COUNT).step_by(2) {
-            let partner_a = self.pick_partner();
-            let mut partner_b = self.pick_partner();
-            while partner_a == partner_b {
-                partner_b = self.pick_partner();
+        self.mutation_rate = self.settings.mutation_schedule.rate(&self.settings, population_diversity(ships, &self.genome.bounds()), self.stagnant_generations);
+    }
+
+    // crossover and mutation of each pair of `parents`, two childs per pair
+    pub fn breed(&mut self, parents: &[Ship], pairs: &[(usize, usize)]) -> Vec<Ship> {
+        // each pair draws from its own stream, childs do not depend on the number of threads
+        let stream_seed: u64 = self.rng.gen();
+        let bounds = self.genome.bounds();
+        let max_size = self.settings.chromosome_max_size.max(self.settings.chromosome_size);
+        let game: &Game = self;
+        return pairs.par_iter().enumerate().flat_map_iter(|(index, (partner_a, partner_b))| {
+            let mut rng = StdRng::seed_from_u64(stream_seed.wrapping_add(index as u64));
+            let [mut genes_a, mut genes_b] = game.crossover.cross(&parents[*partner_a], &parents[*partner_b], &mut rng);
+            game.mutation.mutate(&mut genes_a, game.mutation_rate, &mut rng);
+            game.mutation.mutate(&mut genes_b, game.mutation_rate, &mut rng);
+            mutate_length(&mut genes_a, game.settings.length_mutation_rate, &bounds, max_size, &mut rng);
+            mutate_length(&mut genes_b, game.settings.length_mutation_rate, &bounds, max_size, &mut rng);
+            vec![
+                Ship::from_chromosome(&game.level_data, Chromosome::from_genes(genes_a)),
+                Ship::from_chromosome(&game.level_data, Chromosome::from_genes(genes_b))
+            ]
+        }).collect::<Vec<Ship>>();
+    }
+
+    pub fn generate(&mut self) {
+        let ships = std::mem::take(&mut self.ships);
+        self.update_mutation_rate(&ships);
+        let fitness = match self.settings.niching {
+            Niching::Sharing => shared_fitness(&ships, self.settings.diversity_metric, self.settings.niche_radius, self.settings.sharing_alpha),
+            _ => ships.iter().map(|ship| ship.chromosome.fitness).collect::<Vec<f32>>()
+        };
+        let pair_count = self.settings.population_count.div_ceil(2);
+        let parents = self.selection.select(&fitness, pair_count * 2, &mut self.rng);
+
+        let mut pairs: Vec<(usize, usize)> = vec![];
+        for pair in parents.chunks_exact(2) {
+            let partner_a = pair[0];
+            let mut partner_b = pair[1];
+            if self.settings.niching == Niching::Restricted {
+                let candidates = self.selection.select(&fitness, self.settings.mating_candidates.max(1), &mut self.rng);
+                partner_b = pick_mate(&ships, partner_a, &candidates, self.settings.diversity_metric, self.settings.niche_radius).unwrap_or(partner_b);
+            } else {
+                let mut tries = 0;
+                while ships[partner_a] == ships[partner_b] && tries < ships.len() {
+                    partner_b = self.selection.select(&fitness, 1, &mut self.rng)[0];
+                    tries += 1;
+                }
             }
-            let mut childs: [Ship; 2] = partner_a.crossover(partner_b, &self.level_data, &mut self.rng);
-            childs[0].mutate(self.mutation_rate, &mut self.rng);
-            childs[1].mutate(self.mutation_rate, &mut self.rng);
-            new_ships.push(childs[0].clone());
-            new_ships.push(childs[1].clone());
+            pairs.push((partner_a, partner_b));
         }
-        self.ships = new_ships;
+
+        self.ships = self.breed(&ships, &pairs);
         self.generation += 1;
     }
 
-    fn calc_min_dist(&self, crash_pos: &Pos, crash_zone_index: usize) -> f32 {
+    pub fn calc_min_dist(&self, crash_pos: &Pos, crash_zone_index: usize) -> f32 {
         if crash_zone_index == self.landing_zone_index {
             return 0.0;
         }
@@ -290,49 +602,106 @@ impl Game {
         return dist + offset;
     }
 
-    fn calc_fitness(&mut self, ship_index: usize) {
-        if self.ships[ship_index].is_out {
-            self.ships[ship_index].chromosome.fitness = 1.0;
-        } else if self.ships[ship_index].crash_zone_index != self.landing_zone_index {
-            // eprintln!("A");
-            let dist = self.calc_min_dist(&self.ships[ship_index].crash_pos, self.ships[ship_index].crash_zone_index);
-            let dist_score = scale(dist, 0.0, self.surface_length as f32, 99.0, 0.0); // 0 to 99.0
-            let speed = ((self.ships[ship_index].h_speed * self.ships[ship_index].h_speed) + (self.ships[ship_index].v_speed * self.ships[ship_index].v_speed)).sqrt(); // 0 to 707.106781187
-            let mut speed_score = 0.0;
-            if speed > 100.0 {
-                speed_score = 0.1 * speed;
-            }
-            self.ships[ship_index].chromosome.fitness = 1.0 + dist_score - speed_score; // 1 to 100.0
-        } else if !self.ships[ship_index].is_solution {
-            // eprintln!("B");
-            let mut x_score = 50.0;
-            if (self.ships[ship_index].h_speed).abs() > 20.0 {
-                x_score = scale(self.ships[ship_index].h_speed.abs(), 500.0, 20.0, 0.0, 50.0); // 0 to 50.0
-            }
-            let mut y_score = 50.0;
-            if self.ships[ship_index].v_speed < -40.0 {
-                y_score = scale(self.ships[ship_index].v_speed, -500.0, -40.0, 0.0, 50.0); // 0 to 50.0
-            }
-            // let angle_score = scale(self.ships[ship_index].angle, -90.0, 90.0, 0.0, 5.0); // 0 to 5.0
-            self.ships[ship_index].chromosome.fitness = 100.0 + x_score + y_score/* - angle_score*/; // 100 to 200
-        } else {
-            let fuel_score = scale(self.ships[ship_index].fuel, 0.0, self.level_data.fuel, 0.0, 100.0); // 0 to 100.0
-            self.ships[ship_index].chromosome.fitness = 200.0 + fuel_score; // 200 to 300
-        }
-    }
-    
     pub fn evaluate(&mut self) {
         let mut max_fitness: f32 = 0.0;
         let mut total_fitness: f32 = 0.0;
     
-        for i in 0..POPULATION_COUNT {
-            self.calc_fitness(i);
+        let game: &Game = self;
+        let breakdowns = self.ships.par_iter().map(|ship| game.fitness.score(game, ship)).collect::<Vec<FitnessBreakdown>>();
+        let mut best_index = 0;
+        for (i, breakdown) in breakdowns.into_iter().enumerate() {
+            self.ships[i].chromosome.fitness = breakdown.total();
+            self.ships[i].chromosome.breakdown = breakdown;
             if self.ships[i].chromosome.fitness > max_fitness {
                 max_fitness = self.ships[i].chromosome.fitness;
+                best_index = i;
             }
             total_fitness += self.ships[i].chromosome.fitness;
+            if self.ships[i].is_solution && (self.best_ship.is_none() || self.ships[i].chromosome.fitness > self.best_ship.as_ref().unwrap().chromosome.fitness) {
+                self.best_ship = Some(self.ships[i].clone());
+            }
+        }
+        if max_fitness > self.best_fitness {
+            self.best_fitness = max_fitness;
+            self.stagnant_generations = 0;
+        } else {
+            self.stagnant_generations += 1;
+        }
+        if !self.verbose {
+            return;
         }
-        let fitness_average: i32 = (total_fitness / POPULATION_COUNT as f32) as i32;
-        eprintln!("gen: {} | av: {} | max: {}", self.generation, fitness_average, max_fitness as i32);
+        let fitness_average: i32 = (total_fitness / self.ships.len() as f32) as i32;
+        let prefix = if self.settings.island_count > 1 { format!("island: {} | ", self.island) } else { String::new() };
+        let restart = match self.restart_log.take() {
+            Some(restart) => format!(" | restart: {}", restart),
+            None => String::new()
+        };
+        eprintln!("{}gen: {} | av: {} | max: {} | mutation: {:.3}{}", prefix, self.generation, fitness_average, max_fitness as i32, self.mutation_rate, restart);
+        eprintln!("    best: {}", self.ships[best_index].chromosome.breakdown);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsing::parser::{parse_file};
+
+    // a few generations of a seeded search on a pool of `threads` threads
+    fn search(threads: usize) -> Game {
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+        return pool.install(|| {
+            let level_data = parse_file("levels/01").unwrap();
+            let settings = Settings { seed: Some(7), population_count: 40, ..Settings::default() };
+            let mut game = Game::setup(&level_data, &settings);
+            for _ in 0..6 {
+                game.run_generation();
+            }
+            game.fly_population();
+            return game;
+        });
+    }
+
+    #[test]
+    fn a_seed_gives_the_same_search_on_any_number_of_threads() {
+        let (serial, parallel) = (search(1), search(4));
+        let best = |game: &Game| game.ships.iter().max_by(|a, b| a.chromosome.fitness.total_cmp(&b.chromosome.fitness)).unwrap().chromosome.clone();
+        assert_eq!(best(&serial), best(&parallel));
+        let genes = |game: &Game| game.ships.iter().map(|ship| ship.chromosome.genes.clone()).collect::<Vec<Vec<(i32, i32)>>>();
+        assert_eq!(genes(&serial), genes(&parallel));
+    }
+
+    // the ship after the first turn of the plan, from the start of the level
+    fn first_turn(game: &Game, plan: &Ship) -> Ship {
+        let start = (game.level_data.angle as i32, game.level_data.power as i32);
+        let target = game.genome.decode(&plan.chromosome.genes, start.0, start.1)[0];
+        let mut ship = Ship::from_chromosome(&game.level_data, Chromosome::from_genes(vec![]));
+        let (angle, power) = (target.0 as f32 - ship.angle, target.1 as f32 - ship.power);
+        game.step_ship(&mut ship, angle, power);
+        return ship;
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn a_resync_keeps_the_elites_that_still_land() {
+        let level_data = parse_file("levels/01").unwrap();
+        let settings = Settings { seed: Some(5), ..Settings::default() };
+        let mut game = Game::setup(&level_data, &settings);
+        let elite = game.evaluate_genes(crate::game::pilot::pilot_genes(&game).unwrap());
+        assert!(elite.is_solution);
+        let observed = first_turn(&game, &elite);
+        game.advance(&observed);
+        let kept = game.resync(std::slice::from_ref(&elite));
+        assert_eq!(kept.len(), 1);
+        assert!(kept[0].is_solution);
+        assert_eq!(game.ships[0].chromosome.genes, kept[0].chromosome.genes);
+        assert_eq!(game.best_ship.as_ref().map(|best| best.chromosome.fitness), Some(kept[0].chromosome.fitness));
+
+        // pushed far off the plan, the elite no longer lands
+        let mut game = Game::setup(&level_data, &settings);
+        let mut observed = first_turn(&game, &elite);
+        observed.h_speed += 60.0;
+        observed.v_speed -= 40.0;
+        game.advance(&observed);
+        assert!(game.resync(&[elite]).is_empty());
+        assert!(game.best_ship.is_none());
+    }
+}
@@ -0,0 +1,256 @@
+use std::str::FromStr;
+
+use rand::prelude::*;
+use serde::Deserialize;
+
+use crate::parsing::settings::{Settings};
+
+// a chromosome is a list of (i32, i32) genes, the genome tells what they mean:
+// it decodes them into the (angle, power) the ship aims at on each turn, the
+// simulation then turns and throttles toward it at the allowed rate
+pub trait Genome: Send + Sync {
+    // lowest and highest value of the two values of a gene
+    fn bounds(&self) -> [(i32, i32); 2];
+    // target (angle, power) of each turn, from the initial angle and power of the ship
+    fn decode(&self, genes: &[(i32, i32)], angle: i32, power: i32) -> Vec<(i32, i32)>;
+    // gene aiming at `target` on a single turn, after aiming at `previous`,
+    // None when a gene spans several turns
+    fn encode_turn(&self, previous: (i32, i32), target: (i32, i32)) -> Option<(i32, i32)>;
+    // a gene changes the targets of every following turn
+    fn is_cumulative(&self) -> bool {
+        return false;
+    }
+    // number of genes played during the first `turns` turns
+    fn genes_played(&self, genes: &[(i32, i32)], turns: usize) -> usize {
+        return turns.min(genes.len());
+    }
+    // genes left to play once the first turn is flown
+    fn shift(&self, genes: &[(i32, i32)]) -> Vec<(i32, i32)> {
+        return genes[self.genes_played(genes, 1)..].to_vec();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GenomeKind {
+    Delta,
+    Segments,
+    Targets
+}
+
+impl FromStr for GenomeKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s {
+            "delta" => Ok(GenomeKind::Delta),
+            "segments" => Ok(GenomeKind::Segments),
+            "targets" => Ok(GenomeKind::Targets),
+            _ => Err(format!("error: unknown encoding: {}", s))
+        };
+    }
+}
+
+impl GenomeKind {
+    pub fn build(&self, settings: &Settings) -> Box<dyn Genome> {
+        return match self {
+            GenomeKind::Delta => Box::new(Delta),
+            GenomeKind::Segments => Box::new(Segments { max_turns: settings.segment_max_turns.max(1) as i32 }),
+            GenomeKind::Targets => Box::new(Targets)
+        };
+    }
+}
+
+pub fn random_gene(bounds: &[(i32, i32); 2], rng: &mut StdRng) -> (i32, i32) {
+    return (rng.gen_range(bounds[0].0..=bounds[0].1), rng.gen_range(bounds[1].0..=bounds[1].1));
+}
+
+pub fn clamp_gene(bounds: &[(i32, i32); 2], gene: (i32, i32)) -> (i32, i32) {
+    return (gene.0.max(bounds[0].0).min(bounds[0].1), gene.1.max(bounds[1].0).min(bounds[1].1));
+}
+
+fn clamp_target(angle: i32, power: i32) -> (i32, i32) {
+    return (angle.clamp(-90, 90), power.clamp(0, 4));
+}
+
+/* --------------------------------------------------------- */
+/* -   DELTA   --------------------------------------------- */
+/* --------------------------------------------------------- */
+
+// one gene per turn, the change of angle and power on that turn
+pub struct Delta;
+
+impl Genome for Delta {
+    fn bounds(&self) -> [(i32, i32); 2] {
+        return [(-15, 15), (-1, 1)];
+    }
+
+    fn decode(&self, genes: &[(i32, i32)], angle: i32, power: i32) -> Vec<(i32, i32)> {
+        let mut target = (angle, power);
+        return genes.iter().map(|gene| {
+            let gene = clamp_gene(&self.bounds(), *gene);
+            target = clamp_target(target.0 + gene.0, target.1 + gene.1);
+            target
+        }).collect::<Vec<(i32, i32)>>();
+    }
+
+    fn encode_turn(&self, previous: (i32, i32), target: (i32, i32)) -> Option<(i32, i32)> {
+        return Some(clamp_gene(&self.bounds(), (target.0 - previous.0, target.1 - previous.1)));
+    }
+
+    fn is_cumulative(&self) -> bool {
+        return true;
+    }
+}
+
+/* --------------------------------------------------------- */
+/* -   SEGMENTS   ------------------------------------------ */
+/* --------------------------------------------------------- */
+
+// each gene holds a target angle and power for 1 to `max_turns` turns,
+// the power and the number of turns share the second value: power + 5 * (turns - 1)
+pub struct Segments {
+    pub max_turns: i32
+}
+
+impl Genome for Segments {
+    fn bounds(&self) -> [(i32, i32); 2] {
+        return [(-90, 90), (0, 5 * self.max_turns - 1)];
+    }
+
+    fn decode(&self, genes: &[(i32, i32)], _angle: i32, _power: i32) -> Vec<(i32, i32)> {
+        let mut targets = vec![];
+        for gene in genes.iter() {
+            let (angle, held) = clamp_gene(&self.bounds(), *gene);
+            for _ in 0..(held / 5 + 1) {
+                targets.push((angle, held % 5));
+            }
+        }
+        return targets;
+    }
+
+    fn encode_turn(&self, _previous: (i32, i32), _target: (i32, i32)) -> Option<(i32, i32)> {
+        return None;
+    }
+
+    fn genes_played(&self, genes: &[(i32, i32)], turns: usize) -> usize {
+        let mut played = 0;
+        let mut count = 0;
+        for gene in genes.iter() {
+            if played >= turns {
+                break;
+            }
+            played += (clamp_gene(&self.bounds(), *gene).1 / 5 + 1) as usize;
+            count += 1;
+        }
+        return count;
+    }
+
+    // the first segment holds its target for one turn less
+    fn shift(&self, genes: &[(i32, i32)]) -> Vec<(i32, i32)> {
+        let mut genes = genes.to_vec();
+        if !genes.is_empty() {
+            let (angle, held) = clamp_gene(&self.bounds(), genes[0]);
+            if held >= 5 {
+                genes[0] = (angle, held - 5);
+            } else {
+                genes.remove(0);
+            }
+        }
+        return genes;
+    }
+}
+
+/* --------------------------------------------------------- */
+/* -   TARGETS   ------------------------------------------- */
+/* --------------------------------------------------------- */
+
+// one gene per turn, the angle and power to reach, the ship slews toward them
+pub struct Targets;
+
+impl Genome for Targets {
+    fn bounds(&self) -> [(i32, i32); 2] {
+        return [(-90, 90), (0, 4)];
+    }
+
+    fn decode(&self, genes: &[(i32, i32)], _angle: i32, _power: i32) -> Vec<(i32, i32)> {
+        return genes.iter().map(|gene| clamp_gene(&self.bounds(), *gene)).collect::<Vec<(i32, i32)>>();
+    }
+
+    fn encode_turn(&self, _previous: (i32, i32), target: (i32, i32)) -> Option<(i32, i32)> {
+        return Some(clamp_gene(&self.bounds(), target));
+    }
+}
+
+/* --------------------------------------------------------- */
+/* -   CONTINUOUS VIEW   ----------------------------------- */
+/* --------------------------------------------------------- */
+
+// each value of each gene mapped to [0, 1] between its bounds, for the
+// optimizers working on real vectors: [angle 0, power 0, angle 1, power 1, ...]
+pub fn to_unit(genes: &[(i32, i32)], bounds: &[(i32, i32); 2]) -> Vec<f32> {
+    let unit = |value: i32, (low, high): (i32, i32)| (value - low) as f32 / (high - low).max(1) as f32;
+    return genes.iter().flat_map(|gene| vec![unit(gene.0, bounds[0]), unit(gene.1, bounds[1])]).collect::<Vec<f32>>();
+}
+
+// values out of [0, 1] are clamped to the bounds
+pub fn from_unit(values: &[f32], bounds: &[(i32, i32); 2]) -> Vec<(i32, i32)> {
+    let value = |unit: f32, (low, high): (i32, i32)| low + (unit.clamp(0.0, 1.0) * (high - low) as f32).round() as i32;
+    return values.chunks_exact(2).map(|pair| (value(pair[0], bounds[0]), value(pair[1], bounds[1]))).collect::<Vec<(i32, i32)>>();
+}
+
+// genes flying the target (angle, power) of each turn from `start`, then holding
+// the last target until `size` turns, None when a gene spans several turns
+pub fn encode_targets(genome: &dyn Genome, start: (i32, i32), targets: &[(i32, i32)], size: usize) -> Option<Vec<(i32, i32)>> {
+    let hold = *targets.last().unwrap_or(&start);
+    let mut previous = start;
+    let mut genes = vec![];
+    for turn in 0..size.max(targets.len()) {
+        let target = if turn < targets.len() { targets[turn] } else { hold };
+        let gene = genome.encode_turn(previous, target)?;
+        previous = genome.decode(&[gene], previous.0, previous.1)[0];
+        genes.push(gene);
+    }
+    return Some(genes);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the shifted genes decoded from the target of the first turn fly the turns left
+    fn check_shift(genome: &dyn Genome, genes: &[(i32, i32)], start: (i32, i32)) {
+        let targets = genome.decode(genes, start.0, start.1);
+        let shifted = genome.shift(genes);
+        assert_eq!(genome.decode(&shifted, targets[0].0, targets[0].1), targets[1..].to_vec());
+    }
+
+    #[test]
+    fn shift_drops_the_turn_flown() {
+        check_shift(&Delta, &[(15, 1), (-5, 1), (0, -1), (10, 0)], (-10, 2));
+        check_shift(&Targets, &[(30, 4), (-20, 3), (0, 0)], (0, 0));
+        let segments = Segments { max_turns: 4 };
+        check_shift(&segments, &[(20, 2 + 5 * 2), (-40, 4), (0, 1 + 5 * 3)], (0, 0));
+        check_shift(&segments, &[(20, 2), (-40, 4 + 5)], (0, 0));
+        assert_eq!(segments.shift(&[(20, 2 + 5 * 2), (-40, 4)]), vec![(20, 2 + 5), (-40, 4)]);
+        assert_eq!(segments.genes_played(&[(20, 2 + 5 * 2), (-40, 4), (0, 1)], 4), 2);
+    }
+
+    #[test]
+    fn decode_clamps_the_targets() {
+        assert_eq!(Delta.decode(&[(15, 1), (15, 1), (40, 3)], 75, 3), vec![(90, 4), (90, 4), (90, 4)]);
+        assert_eq!(Segments { max_turns: 2 }.decode(&[(-120, 9), (10, 20)], 0, 0), vec![(-90, 4), (-90, 4), (10, 4), (10, 4)]);
+    }
+
+    #[test]
+    fn encoded_targets_decode_back() {
+        let targets = vec![(15, 1), (30, 2), (30, 3), (15, 3)];
+        for genome in [&Delta as &dyn Genome, &Targets].iter() {
+            let genes = encode_targets(*genome, (0, 0), &targets, 6).unwrap();
+            let mut expected = targets.clone();
+            expected.extend(vec![(15, 3); 2]);
+            assert_eq!(genome.decode(&genes, 0, 0), expected);
+        }
+        assert!(encode_targets(&Segments { max_turns: 3 }, (0, 0), &targets, 6).is_none());
+    }
+}
@@ -0,0 +1,312 @@
+use std::fmt;
+
+use rand::prelude::*;
+use rand_distr::{Normal};
+
+use super::policy::*;
+use crate::parsing::settings::{Settings};
+
+/* --------------------------------------------------------- */
+/* -   EXPRESSION TREE   ----------------------------------- */
+/* --------------------------------------------------------- */
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Const(f32),
+    Var(usize), // index in the state, see STATE_NAMES
+    Add,
+    Sub,
+    Mul,
+    Div, // protected, x / 0 = 1
+    Min,
+    Max,
+    IfPos // if a > 0 then b else c
+}
+
+const FUNCTIONS: [Op; 7] = [Op::Add, Op::Sub, Op::Mul, Op::Div, Op::Min, Op::Max, Op::IfPos];
+const BINARY: [Op; 6] = [Op::Add, Op::Sub, Op::Mul, Op::Div, Op::Min, Op::Max];
+
+impl Op {
+    fn arity(&self) -> usize {
+        return match self {
+            Op::Const(_) | Op::Var(_) => 0,
+            Op::IfPos => 3,
+            _ => 2
+        };
+    }
+}
+
+fn random_terminal(rng: &mut StdRng) -> Op {
+    return match rng.gen_bool(0.5) {
+        true => Op::Var(rng.gen_range(0..STATE_SIZE)),
+        false => Op::Const((rng.gen_range(-10.0_f32..10.0) * 10.0).round() / 10.0)
+    };
+}
+
+// the tree is stored in prefix order: an operator, then its operands
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tree {
+    pub ops: Vec<Op>
+}
+
+impl Tree {
+    // grows a random tree of at most `depth` levels
+    pub fn random(depth: usize, rng: &mut StdRng) -> Self {
+        let mut ops = vec![];
+        Tree::grow(&mut ops, depth, rng);
+        return Self { ops };
+    }
+
+    fn grow(ops: &mut Vec<Op>, depth: usize, rng: &mut StdRng) {
+        if depth <= 1 || rng.gen_bool(0.3) {
+            ops.push(random_terminal(rng));
+            return;
+        }
+        let op = FUNCTIONS[rng.gen_range(0..FUNCTIONS.len())];
+        ops.push(op);
+        for _ in 0..op.arity() {
+            Tree::grow(ops, depth - 1, rng);
+        }
+    }
+
+    // index after the subtree starting at `start`
+    pub fn end(&self, start: usize) -> usize {
+        let mut missing = 1;
+        let mut index = start;
+        while missing > 0 {
+            missing = missing + self.ops[index].arity() - 1;
+            index += 1;
+        }
+        return index;
+    }
+
+    pub fn depth(&self) -> usize {
+        return self.depth_at(0).0;
+    }
+
+    fn depth_at(&self, index: usize) -> (usize, usize) {
+        let mut next = index + 1;
+        let mut depth = 0;
+        for _ in 0..self.ops[index].arity() {
+            let (child_depth, child_next) = self.depth_at(next);
+            depth = depth.max(child_depth);
+            next = child_next;
+        }
+        return (depth + 1, next);
+    }
+
+    pub fn eval(&self, state: &[f32; STATE_SIZE]) -> f32 {
+        return self.eval_at(0, state).0;
+    }
+
+    fn eval_at(&self, index: usize, state: &[f32; STATE_SIZE]) -> (f32, usize) {
+        let op = self.ops[index];
+        if let Op::Const(value) = op {
+            return (value, index + 1);
+        }
+        if let Op::Var(var) = op {
+            return (state[var], index + 1);
+        }
+        let (a, next) = self.eval_at(index + 1, state);
+        let (b, next) = self.eval_at(next, state);
+        return match op {
+            Op::Add => (a + b, next),
+            Op::Sub => (a - b, next),
+            Op::Mul => (a * b, next),
+            Op::Div => (if b.abs() < 1e-6 { 1.0 } else { a / b }, next),
+            Op::Min => (a.min(b), next),
+            Op::Max => (a.max(b), next),
+            _ => {
+                let (c, next) = self.eval_at(next, state);
+                (if a > 0.0 { b } else { c }, next)
+            }
+        };
+    }
+
+    // replaces the subtree at `start` by `other`
+    fn replace(&self, start: usize, other: &[Op]) -> Tree {
+        let mut ops = self.ops[..start].to_vec();
+        ops.extend_from_slice(other);
+        ops.extend_from_slice(&self.ops[self.end(start)..]);
+        return Tree { ops };
+    }
+
+    fn fmt_at(&self, index: usize, f: &mut fmt::Formatter) -> Result<usize, fmt::Error> {
+        let op = self.ops[index];
+        let symbol = match op {
+            Op::Const(value) => {
+                write!(f, "{}", value)?;
+                return Ok(index + 1);
+            },
+            Op::Var(var) => {
+                write!(f, "{}", STATE_NAMES[var])?;
+                return Ok(index + 1);
+            },
+            Op::Add => "+",
+            Op::Sub => "-",
+            Op::Mul => "*",
+            Op::Div => "/",
+            Op::Min => "min",
+            Op::Max => "max",
+            Op::IfPos => "if"
+        };
+        return match op {
+            Op::Min | Op::Max => {
+                write!(f, "{}(", symbol)?;
+                let next = self.fmt_at(index + 1, f)?;
+                write!(f, ", ")?;
+                let next = self.fmt_at(next, f)?;
+                write!(f, ")")?;
+                Ok(next)
+            },
+            Op::IfPos => {
+                write!(f, "(if ")?;
+                let next = self.fmt_at(index + 1, f)?;
+                write!(f, " > 0 then ")?;
+                let next = self.fmt_at(next, f)?;
+                write!(f, " else ")?;
+                let next = self.fmt_at(next, f)?;
+                write!(f, ")")?;
+                Ok(next)
+            },
+            _ => {
+                write!(f, "(")?;
+                let next = self.fmt_at(index + 1, f)?;
+                write!(f, " {} ", symbol)?;
+                let next = self.fmt_at(next, f)?;
+                write!(f, ")")?;
+                Ok(next)
+            }
+        };
+    }
+}
+
+impl fmt::Display for Tree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_at(0, f)?;
+        return Ok(());
+    }
+}
+
+/* --------------------------------------------------------- */
+/* -   OPERATORS   ----------------------------------------- */
+/* --------------------------------------------------------- */
+
+// a child bigger than `gp_max_size` nodes or deeper than `gp_max_depth` is
+// dropped for its parent, it keeps the trees from bloating
+fn fits(tree: &Tree, settings: &Settings) -> bool {
+    return tree.ops.len() <= settings.gp_max_size && tree.depth() <= settings.gp_max_depth;
+}
+
+// a random subtree of `a` is replaced by a random subtree of `b`
+fn crossover(a: &Tree, b: &Tree, settings: &Settings, rng: &mut StdRng) -> Tree {
+    let start_a = rng.gen_range(0..a.ops.len());
+    let start_b = rng.gen_range(0..b.ops.len());
+    let child = a.replace(start_a, &b.ops[start_b..b.end(start_b)]);
+    return if fits(&child, settings) { child } else { a.clone() };
+}
+
+// a random subtree is grown again, or a single node is changed
+fn mutate(tree: &Tree, settings: &Settings, rng: &mut StdRng) -> Tree {
+    let index = rng.gen_range(0..tree.ops.len());
+    let child = match rng.gen_bool(0.5) {
+        true => tree.replace(index, &Tree::random(3, rng).ops),
+        false => {
+            let mut child = tree.clone();
+            child.ops[index] = match tree.ops[index] {
+                Op::Const(value) => Op::Const(((value + Normal::new(0.0, 1.0).unwrap().sample(rng)) * 10.0).round() / 10.0),
+                Op::Var(_) => Op::Var(rng.gen_range(0..STATE_SIZE)),
+                Op::IfPos => Op::IfPos,
+                _ => BINARY[rng.gen_range(0..BINARY.len())]
+            };
+            child
+        }
+    };
+    return if fits(&child, settings) { child } else { tree.clone() };
+}
+
+/* --------------------------------------------------------- */
+/* -   CONTROLLER   ---------------------------------------- */
+/* --------------------------------------------------------- */
+
+// one tree for the angle to reach, one for the power
+#[derive(Debug, Clone, PartialEq)]
+pub struct Controller {
+    pub rotate: Tree,
+    pub thrust: Tree
+}
+
+impl Policy for Controller {
+    const NAME: &'static str = "gp";
+
+    fn act(&self, state: &[f32; STATE_SIZE]) -> (f32, f32) {
+        return (self.rotate.eval(state), self.thrust.eval(state));
+    }
+
+    fn random(settings: &Settings, rng: &mut StdRng) -> Self {
+        let depth = settings.gp_init_depth.max(1);
+        return Self { rotate: Tree::random(depth, rng), thrust: Tree::random(depth, rng) };
+    }
+
+    fn breed(a: &Self, b: &Self, settings: &Settings, rng: &mut StdRng) -> Self {
+        let mut child = Self {
+            rotate: crossover(&a.rotate, &b.rotate, settings, rng),
+            thrust: crossover(&a.thrust, &b.thrust, settings, rng)
+        };
+        if rng.gen_bool(settings.gp_mutation_rate.clamp(0.0, 1.0) as f64) {
+            child.rotate = mutate(&child.rotate, settings, rng);
+        }
+        if rng.gen_bool(settings.gp_mutation_rate.clamp(0.0, 1.0) as f64) {
+            child.thrust = mutate(&child.thrust, settings, rng);
+        }
+        return child;
+    }
+
+    // parsimony pressure: between two equally good controllers the smallest wins
+    fn penalty(&self, settings: &Settings) -> f32 {
+        return settings.gp_parsimony * (self.rotate.ops.len() + self.thrust.ops.len()) as f32;
+    }
+}
+
+impl fmt::Display for Controller {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "rotate = {}\nthrust = {}", self.rotate, self.thrust);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trees_evaluate_in_prefix_order() {
+        let mut state = [0.0; STATE_SIZE];
+        state[0] = 3.0;
+        state[1] = -2.0;
+        // if x0 > 0 then x0 - (x1 / 0) else max(x1, 4)
+        let tree = Tree { ops: vec![Op::IfPos, Op::Var(0), Op::Sub, Op::Var(0), Op::Div, Op::Var(1), Op::Const(0.0), Op::Max, Op::Var(1), Op::Const(4.0)] };
+        assert_eq!(tree.eval(&state), 2.0);
+        state[0] = -1.0;
+        assert_eq!(tree.eval(&state), 4.0);
+        assert_eq!(tree.depth(), 4);
+        assert_eq!((tree.end(0), tree.end(2), tree.end(7)), (10, 7, 10));
+        assert_eq!(Tree { ops: vec![Op::Const(1.5)] }.depth(), 1);
+    }
+
+    #[test]
+    fn bred_trees_stay_under_the_bloat_caps() {
+        let settings = Settings { gp_init_depth: 3, gp_max_depth: 5, gp_max_size: 15, gp_mutation_rate: 1.0, ..Settings::default() };
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut population = (0..20).map(|_| Controller::random(&settings, &mut rng)).collect::<Vec<Controller>>();
+        for _ in 0..50 {
+            population = (0..population.len()).map(|i| Controller::breed(&population[i], &population[(i + 7) % population.len()], &settings, &mut rng)).collect::<Vec<Controller>>();
+            for controller in population.iter() {
+                for tree in [&controller.rotate, &controller.thrust].iter() {
+                    // trees of 3 levels have at most 13 nodes, the first generation fits and the childs too
+                    assert!(fits(tree, &settings), "{} nodes, depth {}", tree.ops.len(), tree.depth());
+                    assert_eq!(tree.end(0), tree.ops.len());
+                }
+            }
+        }
+    }
+}
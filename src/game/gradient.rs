@@ -0,0 +1,264 @@
+use super::game::*;
+use super::genome::*;
+use crate::maths::autodiff::*;
+use crate::maths::pos::*;
+use crate::maths::utils::{segment_below};
+
+/* --------------------------------------------------------- */
+/* -   SMOOTH SIMULATOR   ---------------------------------- */
+/* --------------------------------------------------------- */
+
+// weight of the landing and terrain penalties against the fuel burnt
+const PENALTY_WEIGHT: f64 = 10.0;
+// meters kept between the ship and the ground before the touchdown
+const CLEARANCE: f64 = 5.0;
+// speeds aimed at under the landing limits, the rounding moves them a little
+const H_SPEED_LIMIT: f64 = 18.0;
+const V_SPEED_LIMIT: f64 = -38.0;
+// the flight is also refined squeezed into these fractions of its turns
+const HORIZONS: [f64; 7] = [1.0, 0.95, 0.9, 0.85, 0.8, 0.75, 0.7];
+// the re-quantised plan is flown every this many steps
+const CHECK_INTERVAL: usize = 10;
+
+// height and slope of the ground right below (`x`, `y`), the nearest segment under
+// the ship and not the ceiling of a cave, the top of the terrain when it is inside it
+fn ground_at(map: &[Pos], x: f32, y: f32) -> (f32, f32) {
+    let below = segment_below(map, &Pos::from(x, y)).or_else(|| segment_below(map, &Pos::from(x, f32::MAX)));
+    return match below {
+        Some((index, ground)) => {
+            let (a, b) = (&map[index], &map[index + 1]);
+            (ground, (b.y - a.y) / (b.x - a.x))
+        },
+        None => (0.0, 0.0)
+    };
+}
+
+// Ship::simulate on the tape for the angle (in units of 15 degrees) and power of
+// each turn, without the rounding of the commands nor the collisions: the loss is
+// the fuel burnt plus squared penalties for flying closer than CLEARANCE to the
+// ground, and for missing the landing zone or its limits on the last turn
+fn loss(game: &Game, tape: &mut Tape, angles: &[Var], powers: &[Var]) -> Var {
+    let level = &game.level_data;
+    let mut x = tape.var(level.pos.x as f64);
+    let mut y = tape.var(level.pos.y as f64);
+    let mut h_speed = tape.var(level.h_speed as f64);
+    let mut v_speed = tape.var(level.v_speed as f64);
+    let mut total = tape.var(0.0);
+    let turns = angles.len();
+    for t in 0..turns {
+        let radians = tape.scale(angles[t], 15f64.to_radians());
+        let cos = tape.cos(radians);
+        let sin = tape.sin(radians);
+        let lift = tape.mul(powers[t], cos);
+        let v_acc = tape.add_const(lift, -game.gravity as f64);
+        let push = tape.mul(powers[t], sin);
+        let h_acc = tape.scale(push, -1.0);
+        let half_v_acc = tape.scale(v_acc, 0.5);
+        let dy = tape.add(v_speed, half_v_acc);
+        y = tape.add(y, dy);
+        v_speed = tape.add(v_speed, v_acc);
+        let half_h_acc = tape.scale(h_acc, 0.5);
+        let dx = tape.add(h_speed, half_h_acc);
+        x = tape.add(x, dx);
+        h_speed = tape.add(h_speed, h_acc);
+        total = tape.add(total, powers[t]);
+
+        if t + 1 < turns {
+            // still flying: CLEARANCE meters above the ground
+            let (ground, slope) = ground_at(&game.map, tape.value(x) as f32, tape.value(y) as f32);
+            let ground = tape.unary(x, ground as f64, slope as f64);
+            let height = tape.sub(y, ground);
+            let close = tape.add_const(height, -CLEARANCE);
+            let close = tape.scale(close, -1.0);
+            let close = tape.hinge_squared(close);
+            total = weighted(tape, total, close);
+            continue;
+        }
+        // touchdown: below the landing zone line, over the landing zone, slow enough
+        let below = tape.add_const(y, -(game.landing_zone_y as f64 - 1.0));
+        let left = tape.add_const(x, -(game.landing_zone_xmin as f64 + 20.0));
+        let left = tape.scale(left, -1.0);
+        let right = tape.add_const(x, -(game.landing_zone_xmax as f64 - 20.0));
+        let fast_right = tape.add_const(h_speed, -H_SPEED_LIMIT);
+        let fast_left = tape.add_const(h_speed, H_SPEED_LIMIT);
+        let fast_left = tape.scale(fast_left, -1.0);
+        let fall = tape.add_const(v_speed, -V_SPEED_LIMIT);
+        let fall = tape.scale(fall, -1.0);
+        for miss in [below, left, right, fast_right, fast_left, fall].iter() {
+            let penalty = tape.hinge_squared(*miss);
+            total = weighted(tape, total, penalty);
+        }
+    }
+    return total;
+}
+
+fn weighted(tape: &mut Tape, total: Var, penalty: Var) -> Var {
+    let penalty = tape.scale(penalty, PENALTY_WEIGHT);
+    return tape.add(total, penalty);
+}
+
+/* --------------------------------------------------------- */
+/* -   REFINEMENT   ---------------------------------------- */
+/* --------------------------------------------------------- */
+
+// keeps the commands legal: power between 0 and 4, angle between -90 and 90,
+// at most 1 power and 15 degrees of change per turn, and straight on the last turn
+fn project(angles: &mut [f64], powers: &mut [f64], start: (f64, f64)) {
+    let turns = angles.len();
+    let (mut angle, mut power) = (start.0 / 15.0, start.1);
+    for t in 0..turns {
+        angles[t] = angles[t].clamp(angle - 1.0, angle + 1.0).clamp(-6.0, 6.0);
+        powers[t] = powers[t].clamp(power - 1.0, power + 1.0).clamp(0.0, 4.0);
+        angle = angles[t];
+        power = powers[t];
+    }
+    // back from the last turn, the angle must be able to reach 0 in time
+    for t in (0..turns).rev() {
+        let reach = (turns - 1 - t) as f64;
+        angles[t] = angles[t].max(-reach).min(reach);
+    }
+}
+
+// commands rounded to integers, still legal
+fn quantise(angles: &[f64], powers: &[f64], start: (i32, i32)) -> Vec<(i32, i32)> {
+    let turns = angles.len();
+    let mut targets = vec![];
+    let (mut angle, mut power) = start;
+    for t in 0..turns {
+        let reach = 15 * (turns - 1 - t) as i32;
+        angle = ((angles[t] * 15.0).round() as i32).clamp(angle - 15, angle + 15).clamp(-reach, reach).clamp(-90, 90);
+        power = (powers[t].round() as i32).clamp(power - 1, power + 1).clamp(0, 4);
+        targets.push((angle, power));
+    }
+    return targets;
+}
+
+// the flight squeezed or stretched to `turns` turns, as a first guess
+fn resample(values: &[f64], turns: usize) -> Vec<f64> {
+    return (0..turns).map(|t| values[(t * values.len() / turns).min(values.len() - 1)]).collect::<Vec<f64>>();
+}
+
+// gradient descent (adam) on the fuel burnt by the flight of `ship` through the
+// smooth simulator, for the flight and copies of it squeezed in fewer turns
+// (HORIZONS), as the time flown weighs the most on the fuel. Every
+// CHECK_INTERVAL steps the commands are rounded to legal integers and flown by
+// the real simulator, the landing with the most fuel is returned if it beats `ship`
+pub fn refine(game: &Game, ship: &Ship, steps: usize, rate: f64) -> Option<Ship> {
+    let level = &game.level_data;
+    let start = (level.angle as i32, level.power as i32);
+    // the genome must hold one command per turn
+    encode_targets(&*game.genome, start, &[], 1)?;
+
+    // angle and power actually flown on each turn
+    let targets = game.genome.decode(&ship.chromosome.genes, start.0, start.1);
+    let mut replay = Ship::from_chromosome(level, Chromosome::from_genes(vec![]));
+    let mut angles = vec![];
+    let mut powers = vec![];
+    for target in targets.iter().take(ship.path.len()) {
+        let (angle, power) = (target.0 as f32 - replay.angle, target.1 as f32 - replay.power);
+        game.step_ship(&mut replay, angle, power);
+        angles.push(replay.angle as f64 / 15.0);
+        powers.push(replay.power as f64);
+    }
+    if angles.is_empty() {
+        return None;
+    }
+
+    let mut best: Option<Ship> = None;
+    for horizon in HORIZONS.iter() {
+        let turns = ((angles.len() as f64 * horizon).round() as usize).max(1);
+        let mut angles = resample(&angles, turns);
+        let mut powers = resample(&powers, turns);
+        project(&mut angles, &mut powers, (level.angle as f64, level.power as f64));
+        descend(game, &mut angles, &mut powers, steps, rate, ship, &mut best);
+    }
+    return best;
+}
+
+fn descend(game: &Game, angles: &mut [f64], powers: &mut [f64], steps: usize, rate: f64, ship: &Ship, best: &mut Option<Ship>) {
+    let level = &game.level_data;
+    let start = (level.angle as i32, level.power as i32);
+    let size = ship.chromosome.genes.len().max(angles.len());
+    let parameters = angles.len() * 2;
+    let (mut m, mut v) = (vec![0.0; parameters], vec![0.0; parameters]);
+    let (beta1, beta2) = (0.9, 0.999);
+    for step in 1..=steps {
+        let mut tape = Tape::new();
+        let angle_vars = angles.iter().map(|a| tape.var(*a)).collect::<Vec<Var>>();
+        let power_vars = powers.iter().map(|p| tape.var(*p)).collect::<Vec<Var>>();
+        let total = loss(game, &mut tape, &angle_vars, &power_vars);
+        let gradient = tape.gradient(total);
+        for (i, var) in angle_vars.iter().chain(power_vars.iter()).enumerate() {
+            let g = tape.grad(&gradient, *var);
+            m[i] = beta1 * m[i] + (1.0 - beta1) * g;
+            v[i] = beta2 * v[i] + (1.0 - beta2) * g * g;
+            let m_hat = m[i] / (1.0 - beta1.powi(step as i32));
+            let v_hat = v[i] / (1.0 - beta2.powi(step as i32));
+            let delta = rate * m_hat / (v_hat.sqrt() + 1e-8);
+            if i < angles.len() {
+                angles[i] -= delta;
+            } else {
+                powers[i - angles.len()] -= delta;
+            }
+        }
+        project(angles, powers, (level.angle as f64, level.power as f64));
+
+        if step % CHECK_INTERVAL == 0 || step == steps {
+            let plan = quantise(angles, powers, start);
+            if let Some(genes) = encode_targets(&*game.genome, start, &plan, size) {
+                let candidate = game.evaluate_genes(genes);
+                let to_beat = best.as_ref().unwrap_or(ship);
+                if candidate.is_solution && candidate.fuel > to_beat.fuel {
+                    *best = Some(candidate);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsing::parser::{parse_file};
+    use crate::parsing::settings::{Settings};
+
+    #[test]
+    fn ground_below_the_ship_and_not_the_ceiling() {
+        // floor at 100, a ceiling from 2000 back to 1000 over it
+        let map = vec![Pos::from(0.0, 100.0), Pos::from(3000.0, 100.0), Pos::from(3000.0, 2000.0), Pos::from(1000.0, 1000.0)];
+        assert_eq!(ground_at(&map, 2000.0, 500.0), (100.0, 0.0));
+        assert_eq!(ground_at(&map, 2000.0, 2500.0), (1500.0, 0.5));
+    }
+
+    #[test]
+    fn loss_gradient_matches_finite_differences() {
+        let level_data = parse_file("levels/01").unwrap();
+        let game = Game::setup(&level_data, &Settings::default());
+        let turns = 30;
+        let angles = (0..turns).map(|t| (t as f64 * 0.7).sin() * 2.0).collect::<Vec<f64>>();
+        let powers = (0..turns).map(|t| 2.0 + (t as f64 * 0.3).cos()).collect::<Vec<f64>>();
+        let evaluate = |angles: &[f64], powers: &[f64]| {
+            let mut tape = Tape::new();
+            let angle_vars = angles.iter().map(|a| tape.var(*a)).collect::<Vec<Var>>();
+            let power_vars = powers.iter().map(|p| tape.var(*p)).collect::<Vec<Var>>();
+            let total = loss(&game, &mut tape, &angle_vars, &power_vars);
+            let gradient = tape.gradient(total);
+            let grads = angle_vars.iter().chain(power_vars.iter()).map(|var| tape.grad(&gradient, *var)).collect::<Vec<f64>>();
+            (tape.value(total), grads)
+        };
+        let (_, grads) = evaluate(&angles, &powers);
+        let eps = 1e-4;
+        for i in [0, 7, 15, 29].iter() {
+            let (mut plus, mut minus) = (angles.clone(), angles.clone());
+            plus[*i] += eps;
+            minus[*i] -= eps;
+            let numeric = (evaluate(&plus, &powers).0 - evaluate(&minus, &powers).0) / (2.0 * eps);
+            assert!((grads[*i] - numeric).abs() <= 1e-3 * numeric.abs().max(1.0), "angle {}: {} vs {}", i, grads[*i], numeric);
+            let (mut plus, mut minus) = (powers.clone(), powers.clone());
+            plus[*i] += eps;
+            minus[*i] -= eps;
+            let numeric = (evaluate(&angles, &plus).0 - evaluate(&angles, &minus).0) / (2.0 * eps);
+            assert!((grads[turns + *i] - numeric).abs() <= 1e-3 * numeric.abs().max(1.0), "power {}: {} vs {}", i, grads[turns + *i], numeric);
+        }
+    }
+}
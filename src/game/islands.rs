@@ -0,0 +1,145 @@
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+use super::game::*;
+use super::selection::{SelectionKind};
+use super::crossover::{CrossoverKind};
+use super::mutation::{MutationKind, MutationSchedule};
+use super::fitness::{DistanceMetric};
+use crate::parsing::parser::{LevelData};
+use crate::parsing::settings::{Settings};
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Topology {
+    Ring, // each island sends its migrants to the next one
+    Full // each island sends its migrants to every other island
+}
+
+impl FromStr for Topology {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s {
+            "ring" => Ok(Topology::Ring),
+            "full" => Ok(Topology::Full),
+            _ => Err(format!("error: unknown topology: {}", s))
+        };
+    }
+}
+
+// operators of one island, unset values are taken from the main settings
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct IslandSettings {
+    pub selection: Option<SelectionKind>,
+    pub crossover: Option<CrossoverKind>,
+    pub mutation: Option<MutationKind>,
+    pub mutation_rate: Option<f32>,
+    pub mutation_schedule: Option<MutationSchedule>,
+    pub distance_metric: Option<DistanceMetric>
+}
+
+impl IslandSettings {
+    pub fn apply(&self, settings: &Settings) -> Settings {
+        let mut island_settings = settings.clone();
+        island_settings.selection = self.selection.unwrap_or(settings.selection);
+        island_settings.crossover = self.crossover.unwrap_or(settings.crossover);
+        island_settings.mutation = self.mutation.unwrap_or(settings.mutation);
+        island_settings.mutation_rate = self.mutation_rate.unwrap_or(settings.mutation_rate);
+        island_settings.mutation_schedule = self.mutation_schedule.unwrap_or(settings.mutation_schedule);
+        island_settings.distance_metric = self.distance_metric.unwrap_or(settings.distance_metric);
+        return island_settings;
+    }
+}
+
+pub fn island_settings(settings: &Settings, island: usize) -> Settings {
+    let mut island_settings = settings.islands.get(island).cloned().unwrap_or_default().apply(settings);
+    island_settings.seed = settings.seed.map(|seed| seed.wrapping_add(island as u64));
+    return island_settings;
+}
+
+/* --------------------------------------------------------- */
+/* -   ARCHIPELAGO   --------------------------------------- */
+/* --------------------------------------------------------- */
+
+// the home island is the game shown in the viewer, the archipelago holds the
+// other islands and swaps the best ships between all of them
+pub struct Archipelago {
+    pub islands: Vec<Game>,
+    pub topology: Topology,
+    pub migration_interval: usize,
+    pub migration_size: usize,
+    pub generation: usize
+}
+
+impl Archipelago {
+    pub fn setup(level_data: &LevelData, settings: &Settings) -> Self {
+        let islands = (1..settings.island_count.max(1)).map(|island| {
+            let mut game = Game::setup(level_data, &island_settings(settings, island));
+            game.island = island;
+            game
+        }).collect::<Vec<Game>>();
+        return Self {
+            islands,
+            topology: settings.topology,
+            migration_interval: settings.migration_interval.max(1),
+            migration_size: settings.migration_size,
+            generation: 0
+        };
+    }
+
+    pub fn run_generation(&mut self, home: &mut Game) {
+        home.run_generation();
+        for island in self.islands.iter_mut() {
+            // the islands log like the game they run beside
+            island.verbose = home.verbose;
+            island.run_generation();
+        }
+        self.generation += 1;
+        if !self.islands.is_empty() && self.generation.is_multiple_of(self.migration_interval) {
+            self.migrate(home);
+        }
+        for island in self.islands.iter() {
+            if let Some(ref ship) = island.best_ship {
+                if home.best_ship.is_none() || ship.chromosome.fitness > home.best_ship.as_ref().unwrap().chromosome.fitness {
+                    home.best_ship = Some(ship.clone());
+                }
+            }
+        }
+    }
+
+    fn migrate(&mut self, home: &mut Game) {
+        let count = self.islands.len() + 1;
+        let emigrants = (0..count).map(|i| {
+            let island = if i == 0 { &*home } else { &self.islands[i - 1] };
+            best_ships(&island.previous_population, self.migration_size)
+        }).collect::<Vec<Vec<Ship>>>();
+        // every island gets all its migrants at once so that two senders don't overwrite each other
+        let mut incoming: Vec<Vec<Ship>> = vec![vec![]; count];
+        for (from, ships) in emigrants.iter().enumerate() {
+            let destinations = match self.topology {
+                Topology::Ring => vec![(from + 1) % count],
+                Topology::Full => (0..count).filter(|to| *to != from).collect::<Vec<usize>>()
+            };
+            for to in destinations {
+                incoming[to].extend(ships.iter().cloned());
+            }
+        }
+        for (to, migrants) in incoming.iter().enumerate() {
+            let island = if to == 0 { &mut *home } else { &mut self.islands[to - 1] };
+            island.receive_migrants(migrants);
+        }
+        if home.verbose {
+            eprintln!("migration: {} ships between {} islands", self.migration_size, count);
+        }
+    }
+}
+
+fn best_ships(ships: &[Ship], count: usize) -> Vec<Ship> {
+    let mut ships = ships.to_vec();
+    ships.sort_by(|a, b| b.chromosome.fitness.total_cmp(&a.chromosome.fitness));
+    ships.truncate(count);
+    return ships;
+}
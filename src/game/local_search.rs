@@ -0,0 +1,137 @@
+use rand::prelude::*;
+use rayon::prelude::*;
+use serde::Deserialize;
+use std::str::FromStr;
+
+use super::game::*;
+use super::genome::clamp_gene;
+use super::nsga::Objective;
+
+/* --------------------------------------------------------- */
+/* -   LOCAL SEARCH   -------------------------------------- */
+/* --------------------------------------------------------- */
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LocalSearch {
+    None,
+    HillClimb, // random gene changes
+    Coordinate // each gene in turn, from a random one
+}
+
+impl FromStr for LocalSearch {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s {
+            "none" => Ok(LocalSearch::None),
+            "hill_climb" => Ok(LocalSearch::HillClimb),
+            "coordinate" => Ok(LocalSearch::Coordinate),
+            _ => Err(format!("error: unknown local search: {}", s))
+        };
+    }
+}
+
+// changes tried on a gene by the coordinate descent, less thrust first
+const MOVES: [(i32, i32); 5] = [(0, -1), (-1, 0), (1, 0), (-5, 0), (5, 0)];
+
+impl LocalSearch {
+    // tries `steps` gene changes on a landing, a change is kept only if the
+    // ship still lands with more fuel left, or as much fuel and a safer touchdown
+    pub fn improve(&self, game: &Game, ship: &Ship, steps: usize, rng: &mut StdRng) -> Ship {
+        let mut best = ship.clone();
+        if *self == LocalSearch::None || !ship.is_solution || ship.path.is_empty() {
+            return best;
+        }
+        let bounds = game.genome.bounds();
+        let mut index = rng.gen_range(0..ship.path.len()) * MOVES.len();
+        let mut step = 0;
+        while step < steps {
+            // only the genes played before the touchdown matter
+            let turns = game.genome.genes_played(&best.chromosome.genes, best.path.len()).max(1);
+            let (gene_index, delta) = match self {
+                LocalSearch::HillClimb => {
+                    let delta = (rng.gen_range(-3..4), rng.gen_range(-1..2));
+                    if delta == (0, 0) {
+                        continue;
+                    }
+                    (rng.gen_range(0..turns), delta)
+                },
+                _ => {
+                    let gene_index = (index / MOVES.len()) % turns;
+                    let delta = MOVES[index % MOVES.len()];
+                    index += 1;
+                    (gene_index, delta)
+                }
+            };
+            step += 1;
+            let mut genes = best.chromosome.genes.clone();
+            let gene = genes[gene_index];
+            genes[gene_index] = clamp_gene(&bounds, (gene.0 + delta.0, gene.1 + delta.1));
+            if genes[gene_index] == gene {
+                continue;
+            }
+            // when genes are changes of angle and thrust, the next gene undoes the
+            // change so that only one turn of the flight is modified
+            if game.genome.is_cumulative() && gene_index + 1 < genes.len() {
+                let next = genes[gene_index + 1];
+                genes[gene_index + 1] = clamp_gene(&bounds, (next.0 - delta.0, next.1 - delta.1));
+            }
+            let candidate = game.evaluate_genes(genes);
+            // at equal fuel a safer touchdown is kept, it leaves room for a later thrust cut
+            let margin = Objective::Margin.value(&candidate) > Objective::Margin.value(&best);
+            if candidate.is_solution && (candidate.fuel > best.fuel || (candidate.fuel == best.fuel && margin)) {
+                best = candidate;
+            }
+        }
+        best.is_elite = ship.is_elite;
+        return best;
+    }
+}
+
+// runs the local search on the `local_search_elites` first elites that land,
+// returns the number of landings and the fuel gained
+pub fn improve_elites(game: &mut Game, elites: &mut [Ship]) -> (usize, f32) {
+    let stream_seed: u64 = game.rng.gen();
+    let game_ref: &Game = game;
+    let (count, gained) = elites.par_iter_mut().take(game.settings.local_search_elites).enumerate()
+        .filter(|(_, ship)| ship.is_solution)
+        .map(|(index, ship)| {
+            let mut rng = StdRng::seed_from_u64(stream_seed.wrapping_add(index as u64));
+            let fuel = ship.fuel;
+            *ship = game_ref.settings.local_search.improve(game_ref, ship, game_ref.settings.local_search_steps, &mut rng);
+            return (1, ship.fuel - fuel);
+        })
+        .reduce(|| (0, 0.0), |a, b| (a.0 + b.0, a.1 + b.1));
+    for ship in elites.iter() {
+        if ship.is_solution && game.best_ship.as_ref().is_none_or(|best| ship.chromosome.fitness > best.chromosome.fitness) {
+            game.best_ship = Some(ship.clone());
+        }
+    }
+    return (count, gained);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::pilot::pilot_genes;
+    use crate::parsing::parser::{parse_file};
+    use crate::parsing::settings::{Settings};
+
+    #[test]
+    fn local_search_never_returns_a_worse_ship() {
+        let level_data = parse_file("levels/01").unwrap();
+        let game = Game::setup(&level_data, &Settings::default());
+        let landing = game.evaluate_genes(pilot_genes(&game).unwrap());
+        assert!(landing.is_solution);
+        let crash = game.evaluate_genes(vec![(15, -1); game.settings.chromosome_size]);
+        for search in [LocalSearch::HillClimb, LocalSearch::Coordinate].iter() {
+            for seed in 0..5 {
+                let mut rng = StdRng::seed_from_u64(seed);
+                let improved = search.improve(&game, &landing, 40, &mut rng);
+                assert!(improved.is_solution && improved.fuel >= landing.fuel, "{:?}: {} < {}", search, improved.fuel, landing.fuel);
+                assert_eq!(search.improve(&game, &crash, 40, &mut rng), crash);
+            }
+        }
+    }
+}
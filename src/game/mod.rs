@@ -1 +1,24 @@
-pub mod game;
\ No newline at end of file
+#[allow(clippy::module_inception)]
+pub mod game;
+pub mod selection;
+pub mod crossover;
+pub mod mutation;
+pub mod fitness;
+pub mod nsga;
+pub mod islands;
+pub mod diversity;
+pub mod restart;
+pub mod local_search;
+pub mod repair;
+pub mod genome;
+pub mod neuro;
+pub mod policy;
+pub mod gp;
+pub mod optimizer;
+pub mod cmaes;
+pub mod de;
+pub mod planner;
+pub mod pilot;
+pub mod solver;
+pub mod gradient;
+pub mod online;
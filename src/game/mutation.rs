@@ -0,0 +1,309 @@
+use std::str::FromStr;
+
+use rand::prelude::*;
+use rand_distr::{Normal};
+use serde::Deserialize;
+
+use super::game::*;
+use super::genome::{random_gene, clamp_gene};
+use crate::parsing::settings::{Settings};
+
+pub trait Mutation: Send + Sync {
+    fn mutate(&self, genes: &mut [(i32, i32)], rate: f32, rng: &mut StdRng);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MutationKind {
+    Uniform,
+    Creep,
+    Gaussian,
+    Block,
+    Swap
+}
+
+impl FromStr for MutationKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s {
+            "uniform" => Ok(MutationKind::Uniform),
+            "creep" => Ok(MutationKind::Creep),
+            "gaussian" => Ok(MutationKind::Gaussian),
+            "block" => Ok(MutationKind::Block),
+            "swap" => Ok(MutationKind::Swap),
+            _ => Err(format!("error: unknown mutation operator: {}", s))
+        };
+    }
+}
+
+impl MutationKind {
+    pub fn build(&self, settings: &Settings) -> Box<dyn Mutation> {
+        let bounds = settings.encoding.build(settings).bounds();
+        return match self {
+            MutationKind::Uniform => Box::new(UniformReset { bounds }),
+            MutationKind::Creep => Box::new(Creep { step: settings.mutation_step.max(1), bounds }),
+            MutationKind::Gaussian => Box::new(Gaussian { sigma: settings.mutation_sigma.max(f32::EPSILON), bounds }),
+            MutationKind::Block => Box::new(Block { size: settings.mutation_block_size.max(1), bounds }),
+            MutationKind::Swap => Box::new(Swap)
+        };
+    }
+}
+
+/* --------------------------------------------------------- */
+/* -   UNIFORM   ------------------------------------------- */
+/* --------------------------------------------------------- */
+
+// replaces the angle or the thrust by a fresh random value
+pub struct UniformReset {
+    pub bounds: [(i32, i32); 2]
+}
+
+impl Mutation for UniformReset {
+    fn mutate(&self, genes: &mut [(i32, i32)], rate: f32, rng: &mut StdRng) {
+        for gene in genes.iter_mut() {
+            if rng.gen_bool(rate as f64) {
+                gene.0 = rng.gen_range(self.bounds[0].0..=self.bounds[0].1);
+            }
+            if rng.gen_bool(rate as f64) {
+                gene.1 = rng.gen_range(self.bounds[1].0..=self.bounds[1].1);
+            }
+        }
+    }
+}
+
+/* --------------------------------------------------------- */
+/* -   CREEP   --------------------------------------------- */
+/* --------------------------------------------------------- */
+
+// nudges the angle by at most `step` degrees and the thrust by one
+pub struct Creep {
+    pub step: i32,
+    pub bounds: [(i32, i32); 2]
+}
+
+impl Mutation for Creep {
+    fn mutate(&self, genes: &mut [(i32, i32)], rate: f32, rng: &mut StdRng) {
+        for gene in genes.iter_mut() {
+            if rng.gen_bool(rate as f64) {
+                gene.0 += rng.gen_range(-self.step..=self.step);
+            }
+            if rng.gen_bool(rate as f64) {
+                gene.1 += rng.gen_range(-1..2);
+            }
+            *gene = clamp_gene(&self.bounds, *gene);
+        }
+    }
+}
+
+/* --------------------------------------------------------- */
+/* -   GAUSSIAN   ------------------------------------------ */
+/* --------------------------------------------------------- */
+
+// sigma is given in degrees, the thrust uses the same sigma relative to its range
+pub struct Gaussian {
+    pub sigma: f32,
+    pub bounds: [(i32, i32); 2]
+}
+
+impl Mutation for Gaussian {
+    fn mutate(&self, genes: &mut [(i32, i32)], rate: f32, rng: &mut StdRng) {
+        let angle_noise = Normal::new(0.0, self.sigma).unwrap();
+        let range = (self.bounds[1].1 - self.bounds[1].0) as f32 / (self.bounds[0].1 - self.bounds[0].0) as f32;
+        let power_noise = Normal::new(0.0, self.sigma * range).unwrap();
+        for gene in genes.iter_mut() {
+            if rng.gen_bool(rate as f64) {
+                gene.0 += angle_noise.sample(rng).round() as i32;
+            }
+            if rng.gen_bool(rate as f64) {
+                gene.1 += power_noise.sample(rng).round() as i32;
+            }
+            *gene = clamp_gene(&self.bounds, *gene);
+        }
+    }
+}
+
+/* --------------------------------------------------------- */
+/* -   BLOCK   --------------------------------------------- */
+/* --------------------------------------------------------- */
+
+// re-rolls `size` consecutive genes, a block starts on each gene with probability `rate`
+pub struct Block {
+    pub size: usize,
+    pub bounds: [(i32, i32); 2]
+}
+
+impl Mutation for Block {
+    fn mutate(&self, genes: &mut [(i32, i32)], rate: f32, rng: &mut StdRng) {
+        let mut i = 0;
+        while i < genes.len() {
+            if rng.gen_bool(rate as f64) {
+                let end = (i + self.size).min(genes.len());
+                for gene in genes[i..end].iter_mut() {
+                    *gene = random_gene(&self.bounds, rng);
+                }
+                i = end;
+            } else {
+                i += 1;
+            }
+        }
+    }
+}
+
+/* --------------------------------------------------------- */
+/* -   SWAP   ---------------------------------------------- */
+/* --------------------------------------------------------- */
+
+pub struct Swap;
+
+impl Mutation for Swap {
+    fn mutate(&self, genes: &mut [(i32, i32)], rate: f32, rng: &mut StdRng) {
+        for i in 0..genes.len() {
+            if rng.gen_bool(rate as f64) {
+                let j = rng.gen_range(0..genes.len());
+                genes.swap(i, j);
+            }
+        }
+    }
+}
+
+/* --------------------------------------------------------- */
+/* -   LENGTH   -------------------------------------------- */
+/* --------------------------------------------------------- */
+
+// with probability `rate` inserts a random gene or removes one, at a random place,
+// then brings the chromosome back between 1 and `max_size` genes
+pub fn mutate_length(genes: &mut Vec<(i32, i32)>, rate: f32, bounds: &[(i32, i32); 2], max_size: usize, rng: &mut StdRng) {
+    if rate > 0.0 && rng.gen_bool(rate.min(1.0) as f64) {
+        if rng.gen_bool(0.5) {
+            let index = rng.gen_range(0..=genes.len());
+            genes.insert(index, random_gene(bounds, rng));
+        } else if !genes.is_empty() {
+            let index = rng.gen_range(0..genes.len());
+            genes.remove(index);
+        }
+    }
+    genes.truncate(max_size.max(1));
+    if genes.is_empty() {
+        genes.push(random_gene(bounds, rng));
+    }
+}
+
+/* --------------------------------------------------------- */
+/* -   RATE SCHEDULE   ------------------------------------- */
+/* --------------------------------------------------------- */
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MutationSchedule {
+    Fixed,
+    Adaptive
+}
+
+impl FromStr for MutationSchedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s {
+            "fixed" => Ok(MutationSchedule::Fixed),
+            "adaptive" => Ok(MutationSchedule::Adaptive),
+            _ => Err(format!("error: unknown mutation schedule: {}", s))
+        };
+    }
+}
+
+impl MutationSchedule {
+    // the adaptive rate goes from `mutation_rate` up to `mutation_rate_max` when the
+    // population loses its diversity or when the best fitness stops improving
+    pub fn rate(&self, settings: &Settings, diversity: f32, stagnant_generations: usize) -> f32 {
+        return match self {
+            MutationSchedule::Fixed => settings.mutation_rate,
+            MutationSchedule::Adaptive => {
+                let low_diversity = (1.0 - diversity / settings.diversity_target.max(f32::EPSILON)).max(0.0);
+                let stagnation = stagnant_generations as f32 / (stagnant_generations + settings.stagnation_window.max(1)) as f32;
+                let pressure = low_diversity.max(stagnation).min(1.0);
+                settings.mutation_rate + (settings.mutation_rate_max - settings.mutation_rate).max(0.0) * pressure
+            }
+        };
+    }
+}
+
+// mean standard deviation of each gene over the population, relative to the
+// deviation of a random population: ~1.0 for random ships, 0.0 once they are all equal
+pub fn population_diversity(ships: &[Ship], bounds: &[(i32, i32); 2]) -> f32 {
+    let size = ships.iter().map(|ship| ship.chromosome.genes.len()).min().unwrap_or(0);
+    if ships.len() < 2 || size == 0 {
+        return 0.0;
+    }
+    // standard deviation of a uniform integer over [low, high]
+    let random_std = |(low, high): (i32, i32)| ((((high - low + 1) * (high - low + 1) - 1) as f32 / 12.0).sqrt()).max(f32::EPSILON);
+    let random_angle_std = random_std(bounds[0]);
+    let random_power_std = random_std(bounds[1]);
+    let count = ships.len() as f32;
+    let mut diversity = 0.0;
+    for i in 0..size {
+        let (mut angle_sum, mut angle_sq, mut power_sum, mut power_sq) = (0.0, 0.0, 0.0, 0.0);
+        for ship in ships.iter() {
+            let (angle, power) = (ship.chromosome.genes[i].0 as f32, ship.chromosome.genes[i].1 as f32);
+            angle_sum += angle;
+            angle_sq += angle * angle;
+            power_sum += power;
+            power_sq += power * power;
+        }
+        let angle_std = (angle_sq / count - (angle_sum / count).powi(2)).max(0.0).sqrt();
+        let power_std = (power_sq / count - (power_sum / count).powi(2)).max(0.0).sqrt();
+        diversity += 0.5 * (angle_std / random_angle_std + power_std / random_power_std);
+    }
+    return diversity / size as f32;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::genome::*;
+
+    #[test]
+    fn mutated_genes_stay_in_bounds() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for genome in [&Delta as &dyn Genome, &Targets, &Segments { max_turns: 5 }].iter() {
+            let bounds = genome.bounds();
+            let mutations: [Box<dyn Mutation>; 5] = [
+                Box::new(UniformReset { bounds }),
+                Box::new(Creep { step: 20, bounds }),
+                Box::new(Gaussian { sigma: 50.0, bounds }),
+                Box::new(Block { size: 3, bounds }),
+                Box::new(Swap)
+            ];
+            for mutation in mutations.iter() {
+                for rate in [0.1, 1.0].iter() {
+                    let mut genes = (0..30).map(|_| random_gene(&bounds, &mut rng)).collect::<Vec<(i32, i32)>>();
+                    mutation.mutate(&mut genes, *rate, &mut rng);
+                    assert_eq!(genes.len(), 30);
+                    assert!(genes.iter().all(|gene| clamp_gene(&bounds, *gene) == *gene), "{:?}", genes);
+                }
+            }
+            let mut genes = vec![random_gene(&bounds, &mut rng)];
+            for _ in 0..200 {
+                mutate_length(&mut genes, 1.0, &bounds, 8, &mut rng);
+                assert!(!genes.is_empty() && genes.len() <= 8);
+                assert!(genes.iter().all(|gene| clamp_gene(&bounds, *gene) == *gene));
+            }
+        }
+    }
+
+    #[test]
+    fn adaptive_rate_stays_between_the_rate_and_its_max() {
+        let settings = Settings { mutation_rate: 0.05, mutation_rate_max: 0.3, ..Settings::default() };
+        let schedule = MutationSchedule::Adaptive;
+        for diversity in [0.0, 0.2, 0.5, 1.0, 3.0].iter() {
+            for stagnant in [0, 1, 10, 1000].iter() {
+                let rate = schedule.rate(&settings, *diversity, *stagnant);
+                assert!((settings.mutation_rate..=settings.mutation_rate_max).contains(&rate), "{} {} {}", diversity, stagnant, rate);
+            }
+        }
+        // all the ships equal: the highest rate, a diverse population making progress: the base rate
+        assert!((schedule.rate(&settings, 0.0, 0) - settings.mutation_rate_max).abs() < 1e-6);
+        assert!((schedule.rate(&settings, 10.0, 0) - settings.mutation_rate).abs() < 1e-6);
+        assert_eq!(MutationSchedule::Fixed.rate(&settings, 0.0, 1000), settings.mutation_rate);
+    }
+}
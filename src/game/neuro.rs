@@ -0,0 +1,125 @@
+use std::fmt;
+
+use rand::prelude::*;
+use rand_distr::{Normal};
+
+use super::policy::*;
+use crate::parsing::settings::{Settings};
+
+/* --------------------------------------------------------- */
+/* -   NETWORK   ------------------------------------------- */
+/* --------------------------------------------------------- */
+
+const OUTPUTS: usize = 2;
+// brings each value of the state roughly between -1 and 1
+const STATE_SCALE: [f32; STATE_SIZE] = [100.0, 100.0, 90.0, 4.0, 2000.0, 3000.0, 7000.0, 3000.0, 3500.0, 1.0];
+
+// fixed topology: state -> hidden (tanh) -> outputs (tanh), every neuron has a bias
+#[derive(Debug, Clone, PartialEq)]
+pub struct Network {
+    pub hidden: usize,
+    pub weights: Vec<f32>
+}
+
+impl Network {
+    pub fn weight_count(hidden: usize) -> usize {
+        return hidden * (STATE_SIZE + 1) + OUTPUTS * (hidden + 1);
+    }
+
+    pub fn new(hidden: usize, rng: &mut StdRng) -> Self {
+        let input_noise = Normal::new(0.0, 1.0 / (STATE_SIZE as f32).sqrt()).unwrap();
+        let hidden_noise = Normal::new(0.0, 1.0 / (hidden.max(1) as f32).sqrt()).unwrap();
+        let split = hidden * (STATE_SIZE + 1);
+        let weights = (0..Network::weight_count(hidden)).map(|i| {
+            if i < split { input_noise.sample(rng) } else { hidden_noise.sample(rng) }
+        }).collect::<Vec<f32>>();
+        return Self { hidden, weights };
+    }
+
+    pub fn forward(&self, inputs: &[f32; STATE_SIZE]) -> [f32; OUTPUTS] {
+        let mut hidden = vec![0.0; self.hidden];
+        for (j, neuron) in hidden.iter_mut().enumerate() {
+            let w = &self.weights[j * (STATE_SIZE + 1)..(j + 1) * (STATE_SIZE + 1)];
+            *neuron = (w[STATE_SIZE] + inputs.iter().zip(w.iter()).map(|(x, w)| x * w).sum::<f32>()).tanh();
+        }
+        let offset = self.hidden * (STATE_SIZE + 1);
+        let mut outputs = [0.0; OUTPUTS];
+        for (k, output) in outputs.iter_mut().enumerate() {
+            let w = &self.weights[offset + k * (self.hidden + 1)..offset + (k + 1) * (self.hidden + 1)];
+            *output = (w[self.hidden] + hidden.iter().zip(w.iter()).map(|(h, w)| h * w).sum::<f32>()).tanh();
+        }
+        return outputs;
+    }
+}
+
+impl Policy for Network {
+    const NAME: &'static str = "neuro";
+
+    fn act(&self, state: &[f32; STATE_SIZE]) -> (f32, f32) {
+        let mut inputs = [0.0; STATE_SIZE];
+        for i in 0..STATE_SIZE {
+            inputs[i] = state[i] / STATE_SCALE[i];
+        }
+        let [rotate, thrust] = self.forward(&inputs);
+        return (rotate * 90.0, (thrust + 1.0) * 2.0);
+    }
+
+    fn random(settings: &Settings, rng: &mut StdRng) -> Self {
+        return Network::new(settings.neuro_hidden.max(1), rng);
+    }
+
+    // blend of the weights, then each weight mutates with `neuro_mutation_rate`
+    fn breed(a: &Self, b: &Self, settings: &Settings, rng: &mut StdRng) -> Self {
+        let noise = Normal::new(0.0, settings.neuro_sigma.max(f32::EPSILON)).unwrap();
+        let mut child = a.clone();
+        for (i, weight) in child.weights.iter_mut().enumerate() {
+            let r: f32 = rng.gen();
+            *weight = r * a.weights[i] + (1.0 - r) * b.weights[i];
+            if rng.gen_bool(settings.neuro_mutation_rate.clamp(0.0, 1.0) as f64) {
+                *weight += noise.sample(rng);
+            }
+        }
+        return child;
+    }
+}
+
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        return write!(f, "network: {} -> {} -> {} ({} weights)", STATE_SIZE, self.hidden, OUTPUTS, self.weights.len());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forward_gives_two_outputs_between_minus_1_and_1() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for hidden in [1, 4, 16].iter() {
+            let network = Network::new(*hidden, &mut rng);
+            assert_eq!(network.weights.len(), Network::weight_count(*hidden));
+            for _ in 0..100 {
+                let mut state = [0.0; STATE_SIZE];
+                for (value, scale) in state.iter_mut().zip(STATE_SCALE.iter()) {
+                    *value = rng.gen_range(-2.0..2.0) * scale;
+                }
+                let outputs = network.forward(&state);
+                assert_eq!(outputs.len(), OUTPUTS);
+                assert!(outputs.iter().all(|output| (-1.0..=1.0).contains(output)), "{:?}", outputs);
+                let (angle, power) = network.act(&state);
+                assert!((-90.0..=90.0).contains(&angle) && (0.0..=4.0).contains(&power));
+            }
+        }
+        // without weights every neuron gives tanh(0)
+        let network = Network { hidden: 3, weights: vec![0.0; Network::weight_count(3)] };
+        assert_eq!(network.forward(&[1.0; STATE_SIZE]), [0.0, 0.0]);
+        // a single hidden neuron copying the first input into both outputs
+        let mut weights = vec![0.0; Network::weight_count(1)];
+        weights[0] = 1.0;
+        weights[STATE_SIZE + 1] = 1.0;
+        weights[STATE_SIZE + 3] = 1.0;
+        let outputs = Network { hidden: 1, weights }.forward(&[0.5; STATE_SIZE]);
+        assert!(outputs.iter().all(|output| (output - 0.5f32.tanh().tanh()).abs() < 1e-6), "{:?}", outputs);
+    }
+}
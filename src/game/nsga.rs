@@ -0,0 +1,229 @@
+use std::str::FromStr;
+
+use rand::prelude::*;
+use serde::Deserialize;
+
+use super::game::*;
+
+// every objective is maximised
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Objective {
+    Fuel, // fuel left
+    Margin, // distance to the speed limits at touchdown, between 0 and 1
+    Time // flight time, fewer turns is better
+}
+
+impl FromStr for Objective {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s {
+            "fuel" => Ok(Objective::Fuel),
+            "margin" => Ok(Objective::Margin),
+            "time" => Ok(Objective::Time),
+            _ => Err(format!("error: unknown objective: {}", s))
+        };
+    }
+}
+
+impl Objective {
+    pub fn value(&self, ship: &Ship) -> f32 {
+        return match self {
+            Objective::Fuel => ship.fuel,
+            Objective::Margin => ((20.0 - ship.h_speed.abs()) / 20.0).min((ship.v_speed + 40.0) / 40.0),
+            Objective::Time => -(ship.path.len() as f32)
+        };
+    }
+}
+
+pub fn objectives(ship: &Ship, objectives: &[Objective]) -> Vec<f32> {
+    return objectives.iter().map(|objective| objective.value(ship)).collect::<Vec<f32>>();
+}
+
+// landings are compared on their objectives, they always beat the other ships
+// which are compared on their scalar fitness (how close they were to land)
+fn dominates(a: &Ship, a_values: &[f32], b: &Ship, b_values: &[f32]) -> bool {
+    return match (a.is_solution, b.is_solution) {
+        (true, false) => true,
+        (false, true) => false,
+        (false, false) => a.chromosome.fitness > b.chromosome.fitness,
+        (true, true) => {
+            a_values.iter().zip(b_values.iter()).all(|(a, b)| a >= b)
+                && a_values.iter().zip(b_values.iter()).any(|(a, b)| a > b)
+        }
+    };
+}
+
+// fast non-dominated sort, returns the fronts from best to worst
+pub fn non_dominated_sort(ships: &[Ship], values: &[Vec<f32>]) -> Vec<Vec<usize>> {
+    let count = ships.len();
+    let mut dominated_by: Vec<Vec<usize>> = vec![vec![]; count];
+    let mut domination_count = vec![0; count];
+    let mut fronts: Vec<Vec<usize>> = vec![vec![]];
+    for i in 0..count {
+        for j in 0..count {
+            if i == j {
+                continue;
+            }
+            if dominates(&ships[i], &values[i], &ships[j], &values[j]) {
+                dominated_by[i].push(j);
+            } else if dominates(&ships[j], &values[j], &ships[i], &values[i]) {
+                domination_count[i] += 1;
+            }
+        }
+        if domination_count[i] == 0 {
+            fronts[0].push(i);
+        }
+    }
+    let mut current = 0;
+    while !fronts[current].is_empty() {
+        let mut next = vec![];
+        for i in fronts[current].iter() {
+            for j in dominated_by[*i].iter() {
+                domination_count[*j] -= 1;
+                if domination_count[*j] == 0 {
+                    next.push(*j);
+                }
+            }
+        }
+        fronts.push(next);
+        current += 1;
+    }
+    fronts.pop();
+    return fronts;
+}
+
+// crowding distance of each ship of `front`, in the same order
+#[allow(clippy::needless_range_loop)] // m indexes the objectives of every ship
+pub fn crowding_distance(front: &[usize], values: &[Vec<f32>]) -> Vec<f32> {
+    let mut distances = vec![0.0; front.len()];
+    if front.len() <= 2 {
+        return vec![f32::INFINITY; front.len()];
+    }
+    let objective_count = values[front[0]].len();
+    for m in 0..objective_count {
+        let mut order = (0..front.len()).collect::<Vec<usize>>();
+        order.sort_by(|a, b| values[front[*a]][m].partial_cmp(&values[front[*b]][m]).unwrap());
+        let min = values[front[order[0]]][m];
+        let max = values[front[order[front.len() - 1]]][m];
+        distances[order[0]] = f32::INFINITY;
+        distances[order[front.len() - 1]] = f32::INFINITY;
+        if max - min <= f32::EPSILON {
+            continue;
+        }
+        for k in 1..(front.len() - 1) {
+            distances[order[k]] += (values[front[order[k + 1]]][m] - values[front[order[k - 1]]][m]) / (max - min);
+        }
+    }
+    return distances;
+}
+
+/* --------------------------------------------------------- */
+/* -   NSGA-II   ------------------------------------------- */
+/* --------------------------------------------------------- */
+
+// parents and childs compete together, the best fronts survive and breed the next childs
+pub fn next_generation(game: &mut Game) {
+    let mut pool = std::mem::take(&mut game.archive);
+    pool.append(&mut game.ships);
+    let values = pool.iter().map(|ship| objectives(ship, &game.settings.objectives)).collect::<Vec<Vec<f32>>>();
+    let fronts = non_dominated_sort(&pool, &values);
+
+    let size = game.settings.population_count;
+    let mut survivors: Vec<(usize, usize, f32)> = vec![]; // index, rank, crowding
+    for (rank, front) in fronts.iter().enumerate() {
+        if survivors.len() >= size {
+            break;
+        }
+        let distances = crowding_distance(front, &values);
+        let mut members = front.iter().cloned().zip(distances).collect::<Vec<(usize, f32)>>();
+        if survivors.len() + members.len() > size {
+            members.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
+            members.truncate(size - survivors.len());
+        }
+        survivors.extend(members.into_iter().map(|(index, distance)| (index, rank, distance)));
+    }
+
+    let parents = survivors.iter().map(|(index, _, _)| pool[*index].clone()).collect::<Vec<Ship>>();
+    game.update_mutation_rate(&parents);
+    let pairs = (0..size.div_ceil(2)).map(|_| {
+        (binary_tournament(&survivors, &mut game.rng), binary_tournament(&survivors, &mut game.rng))
+    }).collect::<Vec<(usize, usize)>>();
+    let mut childs = game.breed(&pool, &pairs);
+    childs.truncate(size);
+
+    game.archive = survivors.iter().map(|(index, rank, _)| {
+        let mut ship = pool[*index].clone();
+        ship.is_elite = *rank == 0;
+        ship
+    }).collect::<Vec<Ship>>();
+    game.previous_population = game.archive.clone();
+    game.ships = childs;
+    game.generation += 1;
+}
+
+// lower rank wins, then the less crowded ship
+fn binary_tournament(survivors: &[(usize, usize, f32)], rng: &mut StdRng) -> usize {
+    let a = &survivors[rng.gen_range(0..survivors.len())];
+    let b = &survivors[rng.gen_range(0..survivors.len())];
+    if a.1 < b.1 || (a.1 == b.1 && a.2 > b.2) {
+        return a.0;
+    }
+    return b.0;
+}
+
+// landings of `ships` that no other landing dominates, sorted on the first objective
+pub fn pareto_front(ships: &[Ship], objective_list: &[Objective]) -> Vec<Ship> {
+    let landings = ships.iter().filter(|ship| ship.is_solution).cloned().collect::<Vec<Ship>>();
+    if landings.is_empty() {
+        return vec![];
+    }
+    let values = landings.iter().map(|ship| objectives(ship, objective_list)).collect::<Vec<Vec<f32>>>();
+    let fronts = non_dominated_sort(&landings, &values);
+    // genes played after the touchdown do not matter, same values means same landing
+    let mut front: Vec<usize> = vec![];
+    for i in fronts[0].iter() {
+        if !front.iter().any(|j| values[*j] == values[*i]) {
+            front.push(*i);
+        }
+    }
+    front.sort_by(|a, b| values[*b][0].partial_cmp(&values[*a][0]).unwrap());
+    return front.iter().map(|i| landings[*i].clone()).collect::<Vec<Ship>>();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maths::pos::{Pos};
+    use crate::parsing::parser::{LevelData};
+
+    fn ship(is_solution: bool, fitness: f32) -> Ship {
+        let level_data = LevelData { pos: Pos::from(0.0, 0.0), angle: 0.0, power: 0.0, h_speed: 0.0, v_speed: 0.0, fuel: 0.0, map: vec![] };
+        let mut ship = Ship::from_chromosome(&level_data, Chromosome::from_genes(vec![]));
+        ship.is_solution = is_solution;
+        ship.chromosome.fitness = fitness;
+        return ship;
+    }
+
+    #[test]
+    fn landings_are_sorted_in_fronts_before_the_crashes() {
+        let ships = vec![ship(true, 0.0), ship(true, 0.0), ship(true, 0.0), ship(true, 0.0), ship(false, 5.0), ship(false, 9.0)];
+        let values = vec![vec![3.0, 1.0], vec![1.0, 3.0], vec![2.0, 2.0], vec![1.0, 1.0], vec![9.0, 9.0], vec![9.0, 9.0]];
+        let mut fronts = non_dominated_sort(&ships, &values);
+        fronts.iter_mut().for_each(|front| front.sort());
+        // the crashes are ranked on their fitness, whatever their objectives
+        assert_eq!(fronts, vec![vec![0, 1, 2], vec![3], vec![5], vec![4]]);
+    }
+
+    #[test]
+    fn crowding_distance_sums_the_normalised_gaps() {
+        let values = vec![vec![0.0, 10.0], vec![1.0, 5.0], vec![3.0, 0.0], vec![2.0, 4.0]];
+        let distances = crowding_distance(&[0, 1, 2, 3], &values);
+        assert!(distances[0].is_infinite() && distances[2].is_infinite());
+        // ship 1: (2 - 0) / 3 + (10 - 4) / 10, ship 3: (3 - 1) / 3 + (5 - 0) / 10
+        assert!((distances[1] - (2.0 / 3.0 + 0.6)).abs() < 1e-5);
+        assert!((distances[3] - (2.0 / 3.0 + 0.5)).abs() < 1e-5);
+        assert!(crowding_distance(&[0, 1], &values).iter().all(|d| d.is_infinite()));
+    }
+}
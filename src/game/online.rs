@@ -0,0 +1,235 @@
+use std::str::FromStr;
+use std::time::{Instant};
+
+use rand::prelude::*;
+use rand_distr::{Normal};
+use serde::Deserialize;
+
+use super::game::*;
+use super::islands::*;
+use crate::parsing::parser::{LevelData};
+use crate::parsing::settings::{Settings};
+
+/* --------------------------------------------------------- */
+/* -   REFEREE   ------------------------------------------- */
+/* --------------------------------------------------------- */
+
+// what the referee sends of the state of the ship on each turn
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Referee {
+    Exact, // the state flown by the simulator
+    Rounding // the position and speeds rounded to integers, as the game sends them
+}
+
+impl FromStr for Referee {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s {
+            "exact" => Ok(Referee::Exact),
+            "rounding" => Ok(Referee::Rounding),
+            _ => Err(format!("error: unknown referee: {}", s))
+        };
+    }
+}
+
+impl Referee {
+    pub fn observe(&self, ship: &Ship) -> Ship {
+        let mut observed = ship.clone();
+        if *self == Referee::Rounding {
+            observed.pos.x = ship.pos.x.round();
+            observed.pos.y = ship.pos.y.round();
+            observed.h_speed = ship.h_speed.round();
+            observed.v_speed = ship.v_speed.round();
+        }
+        return observed;
+    }
+}
+
+// distance in meters and m/s between the state the search predicted and the observed one
+pub fn drift(predicted: &Ship, observed: &Ship) -> (f32, f32) {
+    let position = ((predicted.pos.x - observed.pos.x).powi(2) + (predicted.pos.y - observed.pos.y).powi(2)).sqrt();
+    let speed = ((predicted.h_speed - observed.h_speed).powi(2) + (predicted.v_speed - observed.v_speed).powi(2)).sqrt();
+    return (position, speed);
+}
+
+/* --------------------------------------------------------- */
+/* -   ONLINE REPLANNING   --------------------------------- */
+/* --------------------------------------------------------- */
+
+pub struct OnlineFlight {
+    pub ship: Ship, // the ship flown turn by turn, its genes are the targets it played
+    pub one_shot: Ship, // the best plan of the first turn, flown to the end by the same referee
+    pub steps: usize, // optimizer steps over the whole flight
+    pub resyncs: usize, // turns the elites were flown again from the observed state
+    pub elites: (usize, usize), // elites kept and dropped over every resync
+    pub drifts: Vec<(f32, f32)> // drift of each turn, in meters and m/s
+}
+
+impl OnlineFlight {
+    pub fn max_drift(&self) -> f32 {
+        return self.drifts.iter().fold(0.0, |max, (position, speed)| max.max(position + speed));
+    }
+}
+
+// the best landing of the search, else the best ship of its last step
+fn best_plan(game: &Game) -> Option<Ship> {
+    return game.best_ship.clone().or_else(|| {
+        game.previous_population.iter().max_by(|a, b| a.chromosome.fitness.total_cmp(&b.chromosome.fitness)).cloned()
+    });
+}
+
+// the referee pushes the ship by `noise` m/s on each speed
+fn push(ship: &mut Ship, noise: &Normal<f32>, rng: &mut StdRng) {
+    if !ship.is_dead {
+        ship.h_speed += noise.sample(rng);
+        ship.v_speed += noise.sample(rng);
+    }
+}
+
+// the plan of the first turn flown to the end without replanning, pushed like the online flight
+fn fly_one_shot(game: &Game, level_data: &LevelData, plan: &Ship, noise: &Normal<f32>) -> Ship {
+    let mut rng = StdRng::seed_from_u64(game.seed);
+    let targets = game.genome.decode(&plan.chromosome.genes, level_data.angle as i32, level_data.power as i32);
+    let mut ship = Ship::from_chromosome(level_data, Chromosome::from_genes(vec![]));
+    for target in targets.iter() {
+        if ship.is_dead {
+            break;
+        }
+        let (angle, power) = (target.0 as f32 - ship.angle, target.1 as f32 - ship.power);
+        ship.chromosome.genes.push(*target);
+        game.step_ship(&mut ship, angle, power);
+        push(&mut ship, noise, &mut rng);
+    }
+    return ship;
+}
+
+// plays the level like the referee does: the search has `first_ms` on the first
+// turn then `turn_ms` on each turn, the first target of its best plan is flown
+// by the simulator, then the search goes on with every plan shifted by one turn
+// (rolling horizon) from the state the referee sends. The referee may round it
+// (`online_referee`) and push the ship (`online_noise` m/s on each speed): past
+// `drift_threshold` (meters plus m/s) between the predicted and the observed
+// state, the elites of the last step are flown again from the observed state
+// (see Optimizer::resync). The best plan of the first turn flown to the end
+// through the same referee is the one-shot planning it is compared to
+pub fn play_online(level_data: &LevelData, settings: &Settings, first_ms: u128, turn_ms: u128) -> OnlineFlight {
+    let mut game: Game = Game::setup(level_data, &island_settings(settings, 0));
+    let mut optimizer = settings.optimizer.build(level_data, settings);
+    game.verbose = false;
+    let mut rng = StdRng::seed_from_u64(game.seed);
+    let noise = Normal::new(0.0, settings.online_noise.max(0.0)).expect("error: invalid online noise");
+    let mut ship = Ship::from_chromosome(level_data, Chromosome::from_genes(vec![]));
+    let mut one_shot: Option<Ship> = None;
+    let (mut steps, mut resyncs, mut elites) = (0, 0, (0, 0));
+    let mut drifts: Vec<(f32, f32)> = vec![];
+    while !ship.is_dead && ship.path.len() < settings.online_max_turns {
+        let budget = if ship.path.is_empty() { first_ms } else { turn_ms };
+        let start_time = Instant::now();
+        loop {
+            optimizer.step(&mut game);
+            steps += 1;
+            if start_time.elapsed().as_millis() >= budget {
+                break;
+            }
+        }
+        let plan = best_plan(&game).expect("error: the optimizer flew no ship");
+        if one_shot.is_none() {
+            one_shot = Some(fly_one_shot(&game, level_data, &plan, &noise));
+        }
+        let start = (game.level_data.angle as i32, game.level_data.power as i32);
+        let target = *game.genome.decode(&plan.chromosome.genes, start.0, start.1).first().unwrap_or(&start);
+
+        // the turn as the search predicts it, from the state it plans from
+        let mut predicted = Ship::from_chromosome(&game.level_data, Chromosome::from_genes(vec![]));
+        let (angle, power) = (target.0 as f32 - predicted.angle, target.1 as f32 - predicted.power);
+        game.step_ship(&mut predicted, angle, power);
+        // the turn played by the referee
+        let (angle, power) = (target.0 as f32 - ship.angle, target.1 as f32 - ship.power);
+        ship.chromosome.genes.push(target);
+        game.step_ship(&mut ship, angle, power);
+        push(&mut ship, &noise, &mut rng);
+        let observed = settings.online_referee.observe(&ship);
+
+        let (position, speed) = drift(&predicted, &observed);
+        drifts.push((position, speed));
+        if position + speed > settings.drift_threshold {
+            let (kept, dropped) = optimizer.resync(&mut game, &observed);
+            resyncs += 1;
+            elites = (elites.0 + kept, elites.1 + dropped);
+        } else {
+            optimizer.advance(&mut game, &observed);
+        }
+    }
+    return OnlineFlight { ship, one_shot: one_shot.expect("error: the online flight played no turn"), steps, resyncs, elites, drifts };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::pilot::{pilot_genes};
+    use crate::parsing::parser::{parse_file};
+
+    fn settings(referee: Referee) -> Settings {
+        return Settings { seed: Some(3), population_count: 40, online_referee: referee, online_noise: 0.0, ..Settings::default() };
+    }
+
+    #[test]
+    fn the_referee_rounds_the_position_and_the_speeds() {
+        let level_data = parse_file("levels/01").unwrap();
+        let game = Game::setup(&level_data, &settings(Referee::Exact));
+        let mut ship = Ship::from_chromosome(&level_data, Chromosome::from_genes(vec![]));
+        for _ in 0..5 {
+            game.step_ship(&mut ship, -15.0, 1.0);
+        }
+        let exact = Referee::Exact.observe(&ship);
+        assert_eq!(drift(&ship, &exact), (0.0, 0.0));
+        let rounded = Referee::Rounding.observe(&ship);
+        assert_eq!((rounded.pos.x, rounded.pos.y, rounded.h_speed, rounded.v_speed), (ship.pos.x.round(), ship.pos.y.round(), ship.h_speed.round(), ship.v_speed.round()));
+        assert_eq!((rounded.angle, rounded.power, rounded.fuel), (ship.angle, ship.power, ship.fuel));
+        let (position, speed) = drift(&ship, &rounded);
+        assert!(position < 1.0 && speed < 1.0);
+    }
+
+    #[test]
+    fn the_one_shot_plan_without_noise_flies_like_the_simulator() {
+        let level_data = parse_file("levels/01").unwrap();
+        let game = Game::setup(&level_data, &settings(Referee::Exact));
+        let plan = game.evaluate_genes(pilot_genes(&game).unwrap());
+        let noise = Normal::new(0.0, 0.0).unwrap();
+        let ship = fly_one_shot(&game, &level_data, &plan, &noise);
+        assert!(ship.is_solution);
+        assert_eq!((ship.fuel, ship.pos.x, ship.pos.y), (plan.fuel, plan.pos.x, plan.pos.y));
+    }
+
+    #[test]
+    fn an_exact_referee_without_noise_never_drifts() {
+        let level_data = parse_file("levels/01").unwrap();
+        let flight = play_online(&level_data, &settings(Referee::Exact), 100, 2);
+        assert_eq!(flight.drifts.len(), flight.ship.path.len());
+        assert!(flight.drifts.iter().all(|drift| *drift == (0.0, 0.0)));
+        assert_eq!((flight.max_drift(), flight.resyncs, flight.elites), (0.0, 0, (0, 0)));
+        assert!(flight.ship.is_dead && flight.ship.path.len() <= Settings::default().online_max_turns);
+    }
+
+    #[test]
+    fn a_rounding_referee_drifts_by_its_rounding_only() {
+        let level_data = parse_file("levels/01").unwrap();
+        let flight = play_online(&level_data, &settings(Referee::Rounding), 100, 2);
+        // the search flies each turn from the last observed state, up to half a meter
+        // and half a m/s off on each axis, a meter once the turn is flown, and the
+        // new state is rounded again
+        let axes = 2.0f32.sqrt();
+        assert!(flight.drifts.iter().all(|(position, speed)| *position <= 1.5 * axes && *speed <= axes));
+        assert!(flight.drifts.iter().any(|(position, speed)| *position + *speed > 0.0));
+    }
+
+    #[test]
+    fn the_online_flight_stops_after_online_max_turns() {
+        let level_data = parse_file("levels/01").unwrap();
+        let flight = play_online(&level_data, &Settings { online_max_turns: 10, ..settings(Referee::Exact) }, 20, 1);
+        assert!(flight.ship.path.len() <= 10);
+        assert_eq!(flight.ship.chromosome.genes.len(), flight.ship.path.len());
+    }
+}
@@ -0,0 +1,220 @@
+use std::str::FromStr;
+
+use rand::prelude::*;
+use serde::Deserialize;
+
+use super::game::*;
+use super::islands::*;
+use super::cmaes::*;
+use super::de::*;
+use super::planner::*;
+use crate::parsing::parser::{LevelData};
+use crate::parsing::settings::{Settings};
+
+// a search over the chromosomes of `game`: each step flies and scores a batch of
+// ships with the simulator and the fitness of the game, the caller owns the time budget
+pub trait Optimizer: Send {
+    // one step of the search (a generation for the GA), returns the number of ships flown
+    fn step(&mut self, game: &mut Game) -> usize;
+    // the ship flew the first turn of the plans, the search goes on from the
+    // `observed` state (see Game::advance)
+    fn advance(&mut self, game: &mut Game, observed: &Ship) {
+        game.advance(observed);
+    }
+    // the `observed` state drifted from the predicted one: the search advances from
+    // it and the elites of the last step are flown again from there (see
+    // Game::resync), returns the number of elites kept and dropped
+    fn resync(&mut self, game: &mut Game, observed: &Ship) -> (usize, usize) {
+        let (kept, dropped) = advance_and_resync(self, game, observed);
+        return (kept.len(), dropped);
+    }
+}
+
+// the elites kept, flown from the observed state, and the number dropped, for the
+// optimizers to start again around the kept ones
+pub fn advance_and_resync<O: Optimizer + ?Sized>(optimizer: &mut O, game: &mut Game, observed: &Ship) -> (Vec<Ship>, usize) {
+    let elites = game.last_elites();
+    optimizer.advance(game, observed);
+    let kept = game.resync(&elites);
+    let dropped = elites.len() - kept.len();
+    return (kept, dropped);
+}
+
+// the ship with the best fitness
+pub fn fittest(ships: &[Ship]) -> Option<&Ship> {
+    return ships.iter().max_by(|a, b| a.chromosome.fitness.total_cmp(&b.chromosome.fitness));
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OptimizerKind {
+    Ga,
+    Random,
+    Annealing,
+    Cmaes,
+    De,
+    Beam
+}
+
+impl FromStr for OptimizerKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s {
+            "ga" => Ok(OptimizerKind::Ga),
+            "random" => Ok(OptimizerKind::Random),
+            "annealing" => Ok(OptimizerKind::Annealing),
+            "cmaes" => Ok(OptimizerKind::Cmaes),
+            "de" => Ok(OptimizerKind::De),
+            "beam" => Ok(OptimizerKind::Beam),
+            _ => Err(format!("error: unknown optimizer: {}", s))
+        };
+    }
+}
+
+impl OptimizerKind {
+    pub fn build(&self, level_data: &LevelData, settings: &Settings) -> Box<dyn Optimizer> {
+        return match self {
+            OptimizerKind::Ga => Box::new(Archipelago::setup(level_data, settings)),
+            OptimizerKind::Random => Box::new(RandomSearch),
+            OptimizerKind::Annealing => Box::new(Annealing {
+                temperature: settings.annealing_temperature,
+                cooling: settings.annealing_cooling,
+                current: None
+            }),
+            OptimizerKind::Cmaes => Box::new(SepCmaEs::new(settings)),
+            OptimizerKind::De => Box::new(DifferentialEvolution {
+                f: settings.de_f,
+                cr: settings.de_cr,
+                members: vec![]
+            }),
+            OptimizerKind::Beam => Box::new(BeamPlanner {
+                width: settings.planner_width,
+                macro_turns: settings.planner_macro_turns,
+                beam: vec![]
+            })
+        };
+    }
+}
+
+/* --------------------------------------------------------- */
+/* -   GENETIC ALGORITHM   --------------------------------- */
+/* --------------------------------------------------------- */
+
+// the home game and the other islands each run a generation
+impl Optimizer for Archipelago {
+    fn step(&mut self, game: &mut Game) -> usize {
+        self.run_generation(game);
+        return game.settings.population_count * (self.islands.len() + 1);
+    }
+
+    fn advance(&mut self, game: &mut Game, observed: &Ship) {
+        game.advance(observed);
+        for island in self.islands.iter_mut() {
+            island.advance(observed);
+        }
+    }
+
+    // every island flies its own elites again
+    fn resync(&mut self, game: &mut Game, observed: &Ship) -> (usize, usize) {
+        let island_elites = self.islands.iter().map(|island| island.last_elites()).collect::<Vec<Vec<Ship>>>();
+        let (kept, mut dropped) = advance_and_resync(self, game, observed);
+        let mut kept = kept.len();
+        for (island, elites) in self.islands.iter_mut().zip(island_elites) {
+            let island_kept = island.resync(&elites).len();
+            kept += island_kept;
+            dropped += elites.len() - island_kept;
+        }
+        return (kept, dropped);
+    }
+}
+
+/* --------------------------------------------------------- */
+/* -   RANDOM SEARCH   ------------------------------------- */
+/* --------------------------------------------------------- */
+
+// a fresh batch of random ships on each step, only the best landing is remembered
+pub struct RandomSearch;
+
+impl Optimizer for RandomSearch {
+    fn step(&mut self, game: &mut Game) -> usize {
+        let count = game.settings.population_count;
+        game.ships = (0..count).map(|_| Ship::new(&game.level_data, game.settings.chromosome_size, &*game.genome, &mut game.rng)).collect::<Vec<Ship>>();
+        game.fly_population();
+        game.previous_population = game.ships.clone();
+        game.generation += 1;
+        return count;
+    }
+}
+
+/* --------------------------------------------------------- */
+/* -   SIMULATED ANNEALING   ------------------------------- */
+/* --------------------------------------------------------- */
+
+// each step flies a batch of mutations of the current ship, the best of them
+// replaces it if it is better, or with probability exp(delta / temperature) if
+// it is worse, the temperature is multiplied by `cooling` after each step
+pub struct Annealing {
+    pub temperature: f32,
+    pub cooling: f32,
+    pub current: Option<Ship>
+}
+
+impl Optimizer for Annealing {
+    fn step(&mut self, game: &mut Game) -> usize {
+        let count = game.settings.population_count;
+        game.ships = match self.current {
+            // starts from the best of a random batch
+            None => (0..count).map(|_| Ship::new(&game.level_data, game.settings.chromosome_size, &*game.genome, &mut game.rng)).collect::<Vec<Ship>>(),
+            Some(ref current) => (0..count).map(|_| {
+                let mut genes = current.chromosome.genes.clone();
+                game.mutation.mutate(&mut genes, game.mutation_rate, &mut game.rng);
+                Ship::from_chromosome(&game.level_data, Chromosome::from_genes(genes))
+            }).collect::<Vec<Ship>>()
+        };
+        game.fly_population();
+        game.previous_population = game.ships.clone();
+        game.generation += 1;
+
+        let best = game.ships.iter().max_by(|a, b| a.chromosome.fitness.total_cmp(&b.chromosome.fitness)).cloned();
+        if let Some(best) = best {
+            let accepted = match self.current {
+                None => true,
+                Some(ref current) => {
+                    let delta = best.chromosome.fitness - current.chromosome.fitness;
+                    delta >= 0.0 || game.rng.gen::<f32>() < (delta / self.temperature).exp()
+                }
+            };
+            if accepted {
+                self.current = Some(best);
+            }
+        }
+        self.temperature = (self.temperature * self.cooling).max(f32::EPSILON);
+        if game.verbose {
+            if let Some(ref current) = self.current {
+                eprintln!("    annealing: temperature {:.3} | current {:.1}", self.temperature, current.chromosome.fitness);
+            }
+        }
+        return count;
+    }
+
+    // the current ship is flown again from the observed state
+    fn advance(&mut self, game: &mut Game, observed: &Ship) {
+        game.advance(observed);
+        if let Some(current) = self.current.take() {
+            let genes = game.shift_genes(&current.chromosome.genes);
+            self.current = Some(game.evaluate_genes(genes));
+        }
+    }
+
+    // the best elite kept replaces the current ship when it is better
+    fn resync(&mut self, game: &mut Game, observed: &Ship) -> (usize, usize) {
+        let (kept, dropped) = advance_and_resync(self, game, observed);
+        if let Some(best) = fittest(&kept) {
+            if self.current.as_ref().is_none_or(|current| best.chromosome.fitness > current.chromosome.fitness) {
+                self.current = Some(best.clone());
+            }
+        }
+        return (kept.len(), dropped);
+    }
+}
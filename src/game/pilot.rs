@@ -0,0 +1,108 @@
+use super::game::*;
+use super::genome::*;
+use super::policy::*;
+
+/* --------------------------------------------------------- */
+/* -   REFERENCE PILOT   ----------------------------------- */
+/* --------------------------------------------------------- */
+
+// horizontal speed while cruising toward the pad
+const CRUISE_H_SPEED: f32 = 50.0;
+// horizontal deceleration the pilot plans its braking with
+const BRAKE_ACCELERATION: f32 = 1.0;
+// horizontal speed under which the pilot starts the vertical descent
+const DESCENT_H_SPEED: f32 = 5.0;
+// vertical speed of the descent, under the 40 m/s limit
+const DESCENT_V_SPEED: f32 = -30.0;
+// vertical deceleration the pilot plans its stops with, full power is 4 - 3.711
+const LIFT_ACCELERATION: f32 = 0.2;
+const MAX_ANGLE: f32 = 40.0;
+// tilt at full power still lifting more than the gravity
+const CLIMB_ANGLE: f32 = 20.0;
+// degrees of tilt per m/s of horizontal speed error
+const ANGLE_GAIN: f32 = 1.5;
+// power per m/s of vertical speed error
+const POWER_GAIN: f32 = 0.3;
+
+// highest ground between the ship and the far side of the pad
+fn terrain_top(game: &Game, x: f32) -> f32 {
+    let (low, high) = (x.min(game.landing_zone_xmin), x.max(game.landing_zone_xmax));
+    return game.map.iter()
+        .filter(|point| point.x >= low && point.x <= high)
+        .map(|point| point.y)
+        .fold(ground_y(&game.map, x).max(game.landing_zone_y), f32::max);
+}
+
+// target (angle, power) of the pilot: it cruises toward the pad `clearance`
+// meters above the highest ground on the way, brakes its horizontal speed above
+// the pad, then descends vertically and straightens up before the touchdown
+pub fn pilot_target(game: &Game, ship: &Ship, clearance: f32) -> (i32, i32) {
+    let pad_x = (game.landing_zone_xmin + game.landing_zone_xmax) / 2.0;
+    let margin = ((game.landing_zone_xmax - game.landing_zone_xmin) / 4.0).min(200.0);
+    let over_pad = ship.pos.x >= game.landing_zone_xmin + margin && ship.pos.x <= game.landing_zone_xmax - margin;
+    let height = ship.pos.y - ground_y(&game.map, ship.pos.x);
+
+    // horizontal: cruise speed until the braking distance to the pad, bang-bang in speed
+    let distance = if over_pad { 0.0 } else { (pad_x - ship.pos.x).abs() - (game.landing_zone_xmax - game.landing_zone_xmin) / 2.0 + margin };
+    let desired_h = (pad_x - ship.pos.x).signum() * CRUISE_H_SPEED.min((2.0 * BRAKE_ACCELERATION * distance.max(0.0)).sqrt());
+
+    // vertical: proportional control of the vertical speed around the hover power
+    let desired_v = if over_pad && ship.h_speed.abs() <= DESCENT_H_SPEED {
+        DESCENT_V_SPEED
+    } else {
+        let above = ship.pos.y - terrain_top(game, ship.pos.x) - clearance;
+        if above > 0.0 { -(2.0 * LIFT_ACCELERATION * above).sqrt().min(-DESCENT_V_SPEED) } else { (-above / 10.0).min(20.0) }
+    };
+    // falling too fast, the tilt must leave enough thrust to hold the altitude
+    let max_angle = if ship.v_speed < desired_v - 5.0 { CLIMB_ANGLE } else { MAX_ANGLE };
+    let straighten_turns = (ship.angle.abs() / 15.0).ceil() + 1.0;
+    let angle = if height <= straighten_turns * (-ship.v_speed).max(1.0) + 20.0 {
+        0.0
+    } else {
+        ((ship.h_speed - desired_h) * ANGLE_GAIN).max(-max_angle).min(max_angle)
+    };
+    let hover = game.gravity / angle.to_radians().cos().max(0.5);
+    let power = (hover + POWER_GAIN * (desired_v - ship.v_speed)).round().clamp(0.0, 4.0);
+    return (angle.round() as i32, power as i32);
+}
+
+// flies the level with the pilot for at most `max_turns` turns, the ship keeps
+// the target of each turn as its genes, like the policies (targets genome)
+pub fn fly_pilot(game: &Game, clearance: f32, max_turns: usize) -> Ship {
+    let ship = Ship::from_chromosome(&game.level_data, Chromosome::from_genes(vec![]));
+    return fly_pilot_from(game, ship, clearance, max_turns);
+}
+
+// same from the current state of `ship`, its turns count in `max_turns`
+pub fn fly_pilot_from(game: &Game, mut ship: Ship, clearance: f32, max_turns: usize) -> Ship {
+    while !ship.is_dead && ship.path.len() < max_turns {
+        let target = pilot_target(game, &ship, clearance);
+        ship.chromosome.genes.push(target);
+        let (angle, power) = (target.0 as f32 - ship.angle, target.1 as f32 - ship.power);
+        game.step_ship(&mut ship, angle, power);
+    }
+    let breakdown = game.fitness.score(game, &ship);
+    ship.chromosome.fitness = breakdown.total();
+    ship.chromosome.breakdown = breakdown;
+    return ship;
+}
+
+// the pilot flight in the genes of the game genome, at least `chromosome_size`
+// genes long, None when the genome can't encode it turn by turn
+pub fn pilot_genes(game: &Game) -> Option<Vec<(i32, i32)>> {
+    let ship = fly_pilot(game, game.settings.pilot_clearance, game.settings.policy_max_turns);
+    let start = (game.level_data.angle as i32, game.level_data.power as i32);
+    return encode_targets(&*game.genome, start, &ship.chromosome.genes, game.settings.chromosome_size);
+}
+
+// the first ship flies the pilot plan `genes`, the next `pilot_seeds - 1` fly mutations of it
+pub fn seed_with_pilot(game: &mut Game, genes: &[(i32, i32)]) {
+    let count = game.settings.pilot_seeds.min(game.ships.len());
+    for i in 0..count {
+        let mut seed = genes.to_vec();
+        if i > 0 {
+            game.mutation.mutate(&mut seed, game.settings.mutation_rate.max(0.05), &mut game.rng);
+        }
+        game.ships[i] = Ship::from_chromosome(&game.level_data, Chromosome::from_genes(seed));
+    }
+}
@@ -0,0 +1,160 @@
+use rayon::prelude::*;
+
+use super::game::*;
+use super::genome::*;
+use super::repair::*;
+use super::optimizer::*;
+
+/* --------------------------------------------------------- */
+/* -   BEAM SEARCH   --------------------------------------- */
+/* --------------------------------------------------------- */
+
+// changes of the target angle and power, each macro-action holds its target
+// for `planner_macro_turns` turns
+const ANGLE_MOVES: [i32; 5] = [-30, -15, 0, 15, 30];
+const POWER_MOVES: [i32; 3] = [-1, 0, 1];
+
+// beam search over macro-actions from the start of the level: each step expands
+// every plan of the beam with every macro-action, scores the children with the
+// fitness of the game once completed by the landing controller of the repair, and
+// keeps the `width` best. When the plans reach the chromosome size the search starts over
+// with a beam twice as wide. It only keeps plans, so it can start again from any
+// state: the level data is the state it plans from
+pub struct BeamPlanner {
+    pub width: usize,
+    pub macro_turns: usize,
+    pub beam: Vec<Vec<(i32, i32)>> // target (angle, power) of each turn
+}
+
+impl BeamPlanner {
+    // genes flying `targets`, then holding the last target until `size` turns,
+    // the controller rewrites the held part, None when the genome can't fly the
+    // targets turn by turn
+    fn encode(game: &Game, targets: &[(i32, i32)], size: usize) -> Option<Vec<(i32, i32)>> {
+        let start = (game.level_data.angle as i32, game.level_data.power as i32);
+        return encode_targets(&*game.genome, start, targets, size);
+    }
+}
+
+impl Optimizer for BeamPlanner {
+    fn step(&mut self, game: &mut Game) -> usize {
+        let size = game.settings.chromosome_size;
+        if self.beam.is_empty() || self.beam[0].len() >= size {
+            if !self.beam.is_empty() {
+                self.width *= 2;
+                if game.verbose {
+                    eprintln!("    beam: horizon reached, width {}", self.width);
+                }
+            }
+            self.beam = vec![vec![]];
+        }
+        let start = (game.level_data.angle as i32, game.level_data.power as i32);
+        let mut children = vec![];
+        for plan in self.beam.iter() {
+            let last = *plan.last().unwrap_or(&start);
+            for angle_move in ANGLE_MOVES.iter() {
+                for power_move in POWER_MOVES.iter() {
+                    let target = ((last.0 + angle_move).clamp(-90, 90), (last.1 + power_move).clamp(0, 4));
+                    let mut child = plan.clone();
+                    child.extend(vec![target; self.macro_turns.max(1)]);
+                    child.truncate(size.max(plan.len() + 1));
+                    children.push(child);
+                }
+            }
+        }
+        let game_ref: &Game = game;
+        game.ships = children.par_iter().map(|child| {
+            // a child the genome can't encode does not fly its plan, it is not kept
+            // and the beam falls back on holding the start target below
+            let (played, held) = match (BeamPlanner::encode(game_ref, child, child.len()), BeamPlanner::encode(game_ref, child, size)) {
+                (Some(played), Some(held)) => (played, held),
+                _ => return Ship::from_chromosome(&game_ref.level_data, Chromosome::from_genes(vec![]))
+            };
+            let mut ship = Ship::from_chromosome(&game_ref.level_data, Chromosome::from_genes(played));
+            game_ref.fly(&mut ship);
+            ship.chromosome.genes = held;
+            fly_controller(game_ref, &ship, 30.0, -30.0).unwrap_or(ship)
+        }).collect::<Vec<Ship>>();
+        game.fly_population();
+        game.previous_population = game.ships.clone();
+        game.generation += 1;
+
+        // a child whose ship died before the end of its plan can't be extended
+        let mut order = (0..children.len()).filter(|i| game.ships[*i].path.len() > children[*i].len()).collect::<Vec<usize>>();
+        order.sort_by(|a, b| game.ships[*b].chromosome.fitness.total_cmp(&game.ships[*a].chromosome.fitness));
+        let count = children.len();
+        self.beam = order.into_iter().take(self.width.max(1)).map(|i| std::mem::take(&mut children[i])).collect::<Vec<Vec<(i32, i32)>>>();
+        if self.beam.is_empty() {
+            self.beam = vec![vec![(start.0, start.1); size]];
+        }
+        return count;
+    }
+
+    // the plans lose the target of the turn flown, the new turn searches with the
+    // configured width again: the horizon is reached after almost every turn and
+    // would double the width each time
+    fn advance(&mut self, game: &mut Game, observed: &Ship) {
+        game.advance(observed);
+        self.width = game.settings.planner_width;
+        for plan in self.beam.iter_mut() {
+            if !plan.is_empty() {
+                plan.remove(0);
+            }
+        }
+    }
+
+    // the plans grew from the predicted state: the beam starts again from the root
+    // with the plans of the elites kept, cut to the depth the beam had reached
+    fn resync(&mut self, game: &mut Game, observed: &Ship) -> (usize, usize) {
+        let (mut kept, dropped) = advance_and_resync(self, game, observed);
+        let depth = self.beam.first().map_or(0, |plan| plan.len());
+        kept.sort_by(|a, b| b.chromosome.fitness.total_cmp(&a.chromosome.fitness));
+        let start = (game.level_data.angle as i32, game.level_data.power as i32);
+        let mut beam: Vec<Vec<(i32, i32)>> = vec![];
+        for ship in kept.iter() {
+            let mut plan = game.genome.decode(&ship.chromosome.genes, start.0, start.1);
+            plan.truncate(depth);
+            if !plan.is_empty() && beam.len() < self.width && !beam.contains(&plan) {
+                beam.push(plan);
+            }
+        }
+        self.beam = match beam.is_empty() {
+            true => vec![vec![]],
+            false => beam
+        };
+        return (kept.len(), dropped);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsing::parser::{parse_file};
+    use crate::parsing::settings::{Settings};
+
+    #[test]
+    fn the_beam_keeps_its_width_and_doubles_it_on_the_horizon() {
+        let level_data = parse_file("levels/01").unwrap();
+        let settings = Settings { seed: Some(5), chromosome_size: 20, ..Settings::default() };
+        let mut game = Game::setup(&level_data, &settings);
+        let mut planner = BeamPlanner { width: 4, macro_turns: 5, beam: vec![] };
+        // 4 steps of 5 turns reach the 20 turns of the horizon
+        for depth in 1..=4 {
+            let count = planner.step(&mut game);
+            assert_eq!(count, game.ships.len());
+            assert!(!planner.beam.is_empty() && planner.beam.len() <= 4);
+            assert!(planner.beam.iter().all(|plan| plan.len() == (depth * 5).min(20)));
+            assert_eq!(planner.width, 4);
+        }
+        planner.step(&mut game);
+        assert_eq!(planner.width, 8);
+        assert!(!planner.beam.is_empty() && planner.beam.len() <= 8);
+        assert!(planner.beam.iter().all(|plan| plan.len() == 5));
+        // the genome can't hold the plans: nothing flies them and the beam holds the start target
+        let segments = Settings { encoding: GenomeKind::Segments, ..settings.clone() };
+        let mut game = Game::setup(&level_data, &segments);
+        let mut planner = BeamPlanner { width: 4, macro_turns: 5, beam: vec![] };
+        planner.step(&mut game);
+        assert_eq!(planner.beam, vec![vec![(0, 0); 20]]);
+    }
+}
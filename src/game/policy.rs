@@ -0,0 +1,178 @@
+use std::fmt;
+use std::time::{Instant};
+
+use rand::prelude::*;
+use rayon::prelude::*;
+
+use super::game::*;
+use super::selection::*;
+use crate::maths::pos::*;
+use crate::parsing::parser::{parse_file, LevelData};
+use crate::parsing::settings::{Settings};
+
+// a closed-loop controller: it looks at the ship on each turn and answers the
+// angle and power to reach, the same controller can fly any level
+pub trait Policy: Clone + Send + Sync + fmt::Display {
+    const NAME: &'static str;
+    // target angle and power from the state of the ship (see STATE_NAMES)
+    fn act(&self, state: &[f32; STATE_SIZE]) -> (f32, f32);
+    fn random(settings: &Settings, rng: &mut StdRng) -> Self;
+    // child of two policies, mutated
+    fn breed(a: &Self, b: &Self, settings: &Settings, rng: &mut StdRng) -> Self;
+    // subtracted from the fitness, to keep the policies small
+    fn penalty(&self, _settings: &Settings) -> f32 {
+        return 0.0;
+    }
+}
+
+/* --------------------------------------------------------- */
+/* -   STATE   --------------------------------------------- */
+/* --------------------------------------------------------- */
+
+pub const STATE_SIZE: usize = 10;
+pub const STATE_NAMES: [&str; STATE_SIZE] = [
+    "h_speed", "v_speed", "angle", "power", "fuel", "altitude", "pad_dx", "pad_dy", "pad_half_width", "over_pad"
+];
+
+// height of the ground under `x`
+pub fn ground_y(map: &[Pos], x: f32) -> f32 {
+    for i in 0..(map.len() - 1) {
+        let (a, b) = (&map[i], &map[i + 1]);
+        if x >= a.x.min(b.x) && x <= a.x.max(b.x) {
+            if (b.x - a.x).abs() < f32::EPSILON {
+                return a.y.max(b.y);
+            }
+            return a.y + (b.y - a.y) * (x - a.x) / (b.x - a.x);
+        }
+    }
+    return 0.0;
+}
+
+// ship state and terrain seen by the policies, over_pad is 1 above the landing zone and 0 elsewhere
+pub fn state(game: &Game, ship: &Ship) -> [f32; STATE_SIZE] {
+    let pad_x = (game.landing_zone_xmin + game.landing_zone_xmax) / 2.0;
+    let pad_half_width = (game.landing_zone_xmax - game.landing_zone_xmin) / 2.0;
+    return [
+        ship.h_speed,
+        ship.v_speed,
+        ship.angle,
+        ship.power,
+        ship.fuel,
+        ship.pos.y - ground_y(&game.map, ship.pos.x),
+        pad_x - ship.pos.x,
+        game.landing_zone_y - ship.pos.y,
+        pad_half_width,
+        if (pad_x - ship.pos.x).abs() <= pad_half_width { 1.0 } else { 0.0 }
+    ];
+}
+
+// flies the level with the policy choosing the angle and power to reach on each
+// turn, the choices are kept as a plan for the targets genome
+pub fn fly_policy<P: Policy>(game: &Game, policy: &P, max_turns: usize) -> Ship {
+    let mut ship = Ship::from_chromosome(&game.level_data, Chromosome::from_genes(vec![]));
+    while !ship.is_dead && ship.path.len() < max_turns {
+        let (angle, power) = policy.act(&state(game, &ship));
+        let target = (
+            if angle.is_finite() { angle.round().clamp(-90.0, 90.0) as i32 } else { 0 },
+            if power.is_finite() { power.round().clamp(0.0, 4.0) as i32 } else { 0 }
+        );
+        let (angle, power) = (target.0 as f32 - ship.angle, target.1 as f32 - ship.power);
+        ship.chromosome.genes.push(target);
+        game.step_ship(&mut ship, angle, power);
+    }
+    let breakdown = game.fitness.score(game, &ship);
+    ship.chromosome.fitness = breakdown.total();
+    ship.chromosome.breakdown = breakdown;
+    return ship;
+}
+
+/* --------------------------------------------------------- */
+/* -   POLICY SEARCH   ------------------------------------- */
+/* --------------------------------------------------------- */
+
+// evolves one policy over several levels, its fitness is the mean over the levels
+pub struct PolicySearch<P: Policy> {
+    pub levels: Vec<(String, Game)>,
+    pub settings: Settings,
+    pub population: Vec<P>,
+    pub fitness: Vec<f32>,
+    pub landings: Vec<usize>,
+    pub generation: usize,
+    pub selection: Box<dyn Selection>,
+    pub rng: StdRng
+}
+
+impl<P: Policy> PolicySearch<P> {
+    // trains on `policy_levels`, or on `level_file` when there are none
+    pub fn setup(level_file: &str, level_data: &LevelData, settings: &Settings) -> Result<Self, String> {
+        let mut levels = vec![];
+        if settings.policy_levels.is_empty() {
+            levels.push((level_file.to_string(), Game::setup(level_data, settings)));
+        }
+        for file in settings.policy_levels.iter() {
+            levels.push((file.clone(), Game::setup(&parse_file(file)?, settings)));
+        }
+        let seed = settings.seed.unwrap_or_else(|| rand::thread_rng().gen());
+        let mut rng = StdRng::seed_from_u64(seed);
+        return Ok(Self {
+            levels,
+            settings: settings.clone(),
+            population: (0..settings.population_count.max(2)).map(|_| P::random(settings, &mut rng)).collect::<Vec<P>>(),
+            fitness: vec![],
+            landings: vec![],
+            generation: 0,
+            selection: settings.selection.build(settings),
+            rng
+        });
+    }
+
+    fn evaluate(&mut self) {
+        let levels = &self.levels;
+        let settings = &self.settings;
+        let scores = self.population.par_iter().map(|policy| {
+            let ships = levels.iter().map(|(_, game)| fly_policy(game, policy, settings.policy_max_turns)).collect::<Vec<Ship>>();
+            let fitness = ships.iter().map(|ship| ship.chromosome.fitness).sum::<f32>() / ships.len() as f32;
+            (fitness - policy.penalty(settings), ships.iter().filter(|ship| ship.is_solution).count())
+        }).collect::<Vec<(f32, usize)>>();
+        self.fitness = scores.iter().map(|score| score.0).collect::<Vec<f32>>();
+        self.landings = scores.iter().map(|score| score.1).collect::<Vec<usize>>();
+    }
+
+    pub fn best(&self) -> usize {
+        return (0..self.population.len()).max_by(|a, b| self.fitness[*a].partial_cmp(&self.fitness[*b]).unwrap()).unwrap_or(0);
+    }
+
+    pub fn run_generation(&mut self) {
+        self.evaluate();
+        let best = self.best();
+        let average = self.fitness.iter().sum::<f32>() / self.fitness.len() as f32;
+        eprintln!("{} gen: {} | av: {} | max: {} | landed: {}/{}", P::NAME, self.generation, average as i32, self.fitness[best] as i32, self.landings[best], self.levels.len());
+
+        let mut order = (0..self.population.len()).collect::<Vec<usize>>();
+        order.sort_by(|a, b| self.fitness[*b].partial_cmp(&self.fitness[*a]).unwrap());
+        let elite_count = ((self.population.len() as f32 * self.settings.elite_percentage) as usize).max(1);
+        let mut next = order[..elite_count].iter().map(|i| self.population[*i].clone()).collect::<Vec<P>>();
+        let parents = self.selection.select(&self.fitness, 2 * (self.population.len() - elite_count), &mut self.rng);
+        for pair in parents.chunks_exact(2) {
+            next.push(P::breed(&self.population[pair[0]], &self.population[pair[1]], &self.settings, &mut self.rng));
+        }
+        self.population = next;
+        self.generation += 1;
+    }
+
+    // runs generations until `time_limit` ms are spent, then evaluates the last one
+    pub fn train(&mut self, time_limit: u128) -> P {
+        let start_time = Instant::now();
+        while start_time.elapsed().as_millis() < time_limit {
+            self.run_generation();
+        }
+        self.evaluate();
+        let best = self.population[self.best()].clone();
+        for (file, game) in self.levels.iter() {
+            let ship = fly_policy(game, &best, self.settings.policy_max_turns);
+            eprintln!("{}: {} | fuel {} | turns {}", file, if ship.is_solution { "landed" } else { "failed" }, ship.fuel, ship.path.len());
+        }
+        eprintln!("{}", best);
+        return best;
+    }
+}
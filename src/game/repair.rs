@@ -0,0 +1,125 @@
+use rayon::prelude::*;
+
+use super::game::*;
+
+/* --------------------------------------------------------- */
+/* -   LANDING REPAIR   ------------------------------------ */
+/* --------------------------------------------------------- */
+
+// tilt used to brake the horizontal speed, in degrees
+const LEANS: [f32; 4] = [0.0, 15.0, 30.0, 45.0];
+// vertical speeds under which the repair raises the thrust
+const BRAKES: [f32; 3] = [-20.0, -30.0, -38.0];
+
+// the ship touched the landing zone too fast or tilted
+pub fn is_near_miss(game: &Game, ship: &Ship) -> bool {
+    return ship.is_dead && !ship.is_solution && !ship.is_out && ship.crash_zone_index == game.landing_zone_index;
+}
+
+// rewrites the genes of the last `repair_turns` turns before the touchdown with a
+// simple controller (lean against the horizontal speed, straighten up before the
+// ground, thrust under a vertical speed), a few variants of it are tried and the
+// landing with the most fuel left is kept
+pub fn repair(game: &Game, ship: &Ship) -> Option<Ship> {
+    let turns = game.settings.repair_turns;
+    if turns == 0 || ship.path.is_empty() {
+        return None;
+    }
+    // a genome with genes spanning several turns can't be rewritten turn by turn
+    game.genome.encode_turn((0, 0), (0, 0))?;
+    let mut starts = vec![turns, turns / 2, turns / 4];
+    starts.dedup();
+    let mut best: Option<Ship> = None;
+    for tail in starts.into_iter().filter(|tail| *tail > 0) {
+        let start = ship.path.len().saturating_sub(tail);
+        let mut prefix = Ship::from_chromosome(&game.level_data, Chromosome::from_genes(ship.chromosome.genes[..start].to_vec()));
+        game.fly(&mut prefix);
+        if prefix.is_dead {
+            continue;
+        }
+        prefix.chromosome.genes = ship.chromosome.genes.clone();
+        for lean in LEANS.iter() {
+            for brake in BRAKES.iter() {
+                let candidate = fly_controller(game, &prefix, *lean, *brake)?;
+                if candidate.is_solution && best.as_ref().is_none_or(|best| candidate.fuel > best.fuel) {
+                    best = Some(candidate);
+                }
+            }
+        }
+    }
+    return best;
+}
+
+// plays the controller from the current turn of `ship` until the touchdown
+pub fn fly_controller(game: &Game, ship: &Ship, lean: f32, brake: f32) -> Option<Ship> {
+    let mut ship = ship.clone();
+    let mut turn = ship.path.len();
+    while !ship.is_dead && turn < ship.chromosome.genes.len() {
+        let height = ship.pos.y - game.landing_zone_y;
+        let straighten_turns = (ship.angle.abs() / 15.0).ceil() + 1.0;
+        let target_angle = if height <= straighten_turns * (-ship.v_speed).max(1.0) {
+            0.0
+        } else if ship.h_speed.abs() > 15.0 {
+            lean * ship.h_speed.signum()
+        } else {
+            0.0
+        };
+        let target_power = if ship.v_speed < brake { 4.0 } else { 2.0 };
+        let current = (ship.angle as i32, ship.power as i32);
+        let gene = game.genome.encode_turn(current, (target_angle as i32, target_power as i32))?;
+        ship.chromosome.genes[turn] = gene;
+        let (angle, power) = game.genome.decode(&[gene], current.0, current.1)[0];
+        game.step_ship(&mut ship, (angle - current.0) as f32, (power - current.1) as f32);
+        turn += 1;
+    }
+    return Some(ship);
+}
+
+// repairs the near misses of the population, returns how many were tried and
+// how many now land
+pub fn repair_near_misses(game: &mut Game) -> (usize, usize) {
+    let mut ships = std::mem::take(&mut game.ships);
+    let game_ref: &Game = game;
+    let (tried, repaired) = ships.par_iter_mut()
+        .filter(|ship| is_near_miss(game_ref, ship))
+        .map(|ship| {
+            match repair(game_ref, ship) {
+                Some(repaired) => {
+                    *ship = repaired;
+                    return (1, 1);
+                },
+                None => return (1, 0)
+            }
+        })
+        .reduce(|| (0, 0), |a, b| (a.0 + b.0, a.1 + b.1));
+    game.ships = ships;
+    return (tried, repaired);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::pilot::pilot_genes;
+    use crate::parsing::parser::{parse_file};
+    use crate::parsing::settings::{Settings};
+
+    #[test]
+    fn a_near_miss_is_repaired_into_a_landing() {
+        let level_data = parse_file("levels/01").unwrap();
+        let game = Game::setup(&level_data, &Settings { repair_turns: 20, ..Settings::default() });
+        let mut genes = pilot_genes(&game).unwrap();
+        let landing = game.evaluate_genes(genes.clone());
+        assert!(landing.is_solution);
+        // the engine cut a few turns before the touchdown: the ship hits the pad too fast
+        let touchdown = landing.path.len();
+        for gene in genes[touchdown - 15..touchdown].iter_mut() {
+            *gene = (0, -1);
+        }
+        let near_miss = game.evaluate_genes(genes);
+        assert!(is_near_miss(&game, &near_miss), "{} {}", near_miss.h_speed, near_miss.v_speed);
+        let repaired = repair(&game, &near_miss).unwrap();
+        assert!(repaired.is_solution && repaired.chromosome.genes.len() == near_miss.chromosome.genes.len());
+        // repair_turns at 0 disables it
+        assert!(repair(&Game::setup(&level_data, &Settings::default()), &near_miss).is_none());
+    }
+}
@@ -0,0 +1,142 @@
+use serde::Deserialize;
+use std::str::FromStr;
+
+use super::game::*;
+
+/* --------------------------------------------------------- */
+/* -   RESTART   ------------------------------------------- */
+/* --------------------------------------------------------- */
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Restart {
+    None,
+    Partial,
+    Hypermutation,
+    Full
+}
+
+impl FromStr for Restart {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s {
+            "none" => Ok(Restart::None),
+            "partial" => Ok(Restart::Partial),
+            "hypermutation" => Ok(Restart::Hypermutation),
+            "full" => Ok(Restart::Full),
+            _ => Err(format!("error: unknown restart: {}", s))
+        };
+    }
+}
+
+// called before each generation, once the best fitness has not improved for
+// `restart_after` generations the population is shaken up
+pub fn check_stagnation(game: &mut Game) {
+    if game.hypermutation_left > 0 {
+        game.hypermutation_left -= 1;
+    }
+    if game.settings.restart == Restart::None || game.stagnant_generations < game.settings.restart_after.max(1) {
+        return;
+    }
+    update_hall_of_fame(game);
+    let name = match game.settings.restart {
+        Restart::None => return,
+        Restart::Partial => {
+            // elites are the first ships, the last ones are replaced
+            let count = ((game.ships.len() as f32 * game.settings.restart_fraction) as usize).min(game.ships.len());
+            let start = game.ships.len() - count;
+            for i in start..game.ships.len() {
+                game.ships[i] = Ship::new(&game.level_data, game.settings.chromosome_size, &*game.genome, &mut game.rng);
+            }
+            "partial"
+        },
+        Restart::Hypermutation => {
+            game.hypermutation_left = game.settings.hypermutation_generations;
+            "hypermutation"
+        },
+        Restart::Full => {
+            let mut ships = game.hall_of_fame.clone();
+            ships.truncate(game.settings.population_count);
+            while ships.len() < game.settings.population_count {
+                ships.push(Ship::new(&game.level_data, game.settings.chromosome_size, &*game.genome, &mut game.rng));
+            }
+            game.ships = ships;
+            game.archive.clear();
+            "full"
+        }
+    };
+    game.stagnant_generations = 0;
+    game.restarts += 1;
+    game.restart_log = Some(format!("{} #{}", name, game.restarts));
+}
+
+// best distinct ships seen before each restart, they survive a full restart
+fn update_hall_of_fame(game: &mut Game) {
+    let mut candidates = std::mem::take(&mut game.hall_of_fame);
+    candidates.extend(game.previous_population.iter().cloned());
+    candidates.sort_by(|a, b| b.chromosome.fitness.total_cmp(&a.chromosome.fitness));
+    let mut hall_of_fame: Vec<Ship> = vec![];
+    for ship in candidates.into_iter() {
+        if hall_of_fame.len() >= game.settings.hall_of_fame_size {
+            break;
+        }
+        if hall_of_fame.iter().all(|other| other.chromosome.genes != ship.chromosome.genes) {
+            hall_of_fame.push(ship);
+        }
+    }
+    game.hall_of_fame = hall_of_fame;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsing::parser::{parse_file};
+    use crate::parsing::settings::{Settings};
+
+    fn stagnant_game(restart: Restart) -> Game {
+        let level_data = parse_file("levels/01").unwrap();
+        let settings = Settings { seed: Some(3), population_count: 20, restart, restart_after: 5, restart_fraction: 0.25, hall_of_fame_size: 3, ..Settings::default() };
+        let mut game = Game::setup(&level_data, &settings);
+        game.stagnant_generations = 5;
+        return game;
+    }
+
+    #[test]
+    fn partial_restart_replaces_the_last_ships() {
+        let mut game = stagnant_game(Restart::Partial);
+        game.ships[0].is_elite = true;
+        game.ships[1].is_elite = true;
+        let before = game.ships.clone();
+        check_stagnation(&mut game);
+        assert_eq!(game.ships.len(), 20);
+        // the elites come first, they are kept with the next ships and the last quarter is replaced
+        assert!((0..15).all(|i| game.ships[i] == before[i]));
+        assert!(game.ships[0].is_elite && game.ships[1].is_elite);
+        assert!((15..20).all(|i| game.ships[i].chromosome.genes != before[i].chromosome.genes));
+        assert_eq!((game.stagnant_generations, game.restarts), (0, 1));
+    }
+
+    #[test]
+    fn full_restart_keeps_the_best_distinct_ships() {
+        let mut game = stagnant_game(Restart::Full);
+        let mut previous = game.ships.clone();
+        for (i, ship) in previous.iter_mut().enumerate() {
+            ship.chromosome.fitness = i as f32;
+        }
+        // the best ship twice, it is kept once
+        previous[18] = previous[19].clone();
+        game.previous_population = previous.clone();
+        check_stagnation(&mut game);
+        assert_eq!(game.ships.len(), 20);
+        let kept = game.hall_of_fame.iter().map(|ship| ship.chromosome.fitness).collect::<Vec<f32>>();
+        assert_eq!(kept, vec![19.0, 17.0, 16.0]);
+        assert!((0..3).all(|i| game.ships[i].chromosome == game.hall_of_fame[i].chromosome));
+        // nothing happens before the search stagnates long enough
+        let mut game = stagnant_game(Restart::Full);
+        game.stagnant_generations = 4;
+        let before = game.ships.clone();
+        check_stagnation(&mut game);
+        assert_eq!((game.ships, game.restarts), (before, 0));
+    }
+}
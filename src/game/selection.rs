@@ -0,0 +1,209 @@
+use std::str::FromStr;
+
+use rand::prelude::*;
+use serde::Deserialize;
+
+use crate::parsing::settings::{Settings};
+
+pub trait Selection: Send + Sync {
+    // returns the indexes of `count` parents picked according to their `fitness`
+    fn select(&self, fitness: &[f32], count: usize, rng: &mut StdRng) -> Vec<usize>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SelectionKind {
+    Roulette,
+    Tournament,
+    Rank,
+    Sus
+}
+
+impl FromStr for SelectionKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return match s {
+            "roulette" => Ok(SelectionKind::Roulette),
+            "tournament" => Ok(SelectionKind::Tournament),
+            "rank" => Ok(SelectionKind::Rank),
+            "sus" => Ok(SelectionKind::Sus),
+            _ => Err(format!("error: unknown selection strategy: {}", s))
+        };
+    }
+}
+
+impl SelectionKind {
+    pub fn build(&self, settings: &Settings) -> Box<dyn Selection> {
+        return match self {
+            SelectionKind::Roulette => Box::new(Roulette),
+            SelectionKind::Tournament => Box::new(Tournament { size: settings.tournament_size }),
+            SelectionKind::Rank => Box::new(Rank { pressure: settings.rank_pressure }),
+            SelectionKind::Sus => Box::new(StochasticUniversal)
+        };
+    }
+}
+
+// fitness can go below zero (crash at high speed), weights are shifted to stay positive
+fn weights(fitness: &[f32]) -> Vec<f32> {
+    let min_fitness = fitness.iter().cloned().fold(f32::INFINITY, f32::min);
+    let offset = if min_fitness < 0.0 { -min_fitness } else { 0.0 };
+    return fitness.iter().map(|value| value + offset).collect::<Vec<f32>>();
+}
+
+// cumulative probabilities, the last one is always 1.0
+fn cumulative(weights: &[f32]) -> Vec<f32> {
+    let sum: f32 = weights.iter().sum();
+    let mut prob_sum = 0.0;
+    let mut probs = weights.iter().map(|weight| {
+        prob_sum += if sum > 0.0 { weight / sum } else { 1.0 / weights.len() as f32 };
+        prob_sum
+    }).collect::<Vec<f32>>();
+    if let Some(last) = probs.last_mut() {
+        *last = 1.0;
+    }
+    return probs;
+}
+
+fn pick_cumulative(probs: &[f32], r: f32) -> usize {
+    return probs.iter().position(|prob| r < *prob).unwrap_or(probs.len() - 1);
+}
+
+/* --------------------------------------------------------- */
+/* -   ROULETTE   ------------------------------------------ */
+/* --------------------------------------------------------- */
+
+pub struct Roulette;
+
+impl Selection for Roulette {
+    fn select(&self, fitness: &[f32], count: usize, rng: &mut StdRng) -> Vec<usize> {
+        let probs = cumulative(&weights(fitness));
+        return (0..count).map(|_| pick_cumulative(&probs, rng.gen())).collect::<Vec<usize>>();
+    }
+}
+
+/* --------------------------------------------------------- */
+/* -   TOURNAMENT   ---------------------------------------- */
+/* --------------------------------------------------------- */
+
+pub struct Tournament {
+    pub size: usize
+}
+
+impl Selection for Tournament {
+    fn select(&self, fitness: &[f32], count: usize, rng: &mut StdRng) -> Vec<usize> {
+        return (0..count).map(|_| {
+            let mut winner = rng.gen_range(0..fitness.len());
+            for _ in 1..self.size {
+                let challenger = rng.gen_range(0..fitness.len());
+                if fitness[challenger] > fitness[winner] {
+                    winner = challenger;
+                }
+            }
+            winner
+        }).collect::<Vec<usize>>();
+    }
+}
+
+/* --------------------------------------------------------- */
+/* -   RANK   ---------------------------------------------- */
+/* --------------------------------------------------------- */
+
+pub struct Rank {
+    pub pressure: f32 // between 1.0 (uniform) and 2.0
+}
+
+impl Selection for Rank {
+    fn select(&self, fitness: &[f32], count: usize, rng: &mut StdRng) -> Vec<usize> {
+        let n = fitness.len();
+        let mut order = (0..n).collect::<Vec<usize>>();
+        order.sort_by(|a, b| fitness[*a].partial_cmp(&fitness[*b]).unwrap());
+
+        // linear ranking, worst ship has rank 0
+        let rank_weights = (0..n).map(|rank| {
+            match n {
+                1 => 1.0,
+                _ => (2.0 - self.pressure) + 2.0 * (self.pressure - 1.0) * rank as f32 / (n - 1) as f32
+            }
+        }).collect::<Vec<f32>>();
+        let probs = cumulative(&rank_weights);
+        return (0..count).map(|_| order[pick_cumulative(&probs, rng.gen())]).collect::<Vec<usize>>();
+    }
+}
+
+/* --------------------------------------------------------- */
+/* -   STOCHASTIC UNIVERSAL SAMPLING   --------------------- */
+/* --------------------------------------------------------- */
+
+pub struct StochasticUniversal;
+
+impl Selection for StochasticUniversal {
+    fn select(&self, fitness: &[f32], count: usize, rng: &mut StdRng) -> Vec<usize> {
+        let probs = cumulative(&weights(fitness));
+        let step = 1.0 / count as f32;
+        let start: f32 = rng.gen::<f32>() * step;
+        let mut selected = vec![];
+        let mut index = 0;
+        for i in 0..count {
+            let pointer = start + i as f32 * step;
+            while index < probs.len() - 1 && probs[index] <= pointer {
+                index += 1;
+            }
+            selected.push(index);
+        }
+        // evenly spaced pointers keep ships sorted, shuffle so pairs are not always neighbours
+        selected.shuffle(rng);
+        return selected;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: &[f32], b: &[f32]) -> bool {
+        return a.len() == b.len() && a.iter().zip(b.iter()).all(|(x, y)| (x - y).abs() < 1e-6);
+    }
+
+    #[test]
+    fn cumulative_probabilities_follow_the_fitness_shares() {
+        // the old roulette added prob_sum twice: [0.1, 0.4, 1.0, 2.2]
+        assert!(close(&cumulative(&[1.0, 2.0, 3.0, 4.0]), &[0.1, 0.3, 0.6, 1.0]));
+        assert!(close(&cumulative(&[0.0, 0.0]), &[0.5, 1.0]));
+    }
+
+    #[test]
+    fn negative_fitness_is_shifted() {
+        assert!(close(&weights(&[-2.0, 0.0, 2.0]), &[0.0, 2.0, 4.0]));
+    }
+
+    #[test]
+    fn pick_cumulative_stays_in_range() {
+        let probs = cumulative(&[1.0, 1.0, 2.0]);
+        assert_eq!(pick_cumulative(&probs, 0.0), 0);
+        assert_eq!(pick_cumulative(&probs, 0.3), 1);
+        assert_eq!(pick_cumulative(&probs, 0.999_999), 2);
+        assert_eq!(pick_cumulative(&probs, 1.0), 2);
+    }
+
+    #[test]
+    fn roulette_picks_in_proportion_to_fitness() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let picks = Roulette.select(&[1.0, 3.0], 20000, &mut rng);
+        let share = picks.iter().filter(|i| **i == 0).count() as f32 / picks.len() as f32;
+        assert!((share - 0.25).abs() < 0.02, "share of the first ship: {}", share);
+    }
+
+    #[test]
+    fn sus_pointers_are_evenly_spaced() {
+        let mut rng = StdRng::seed_from_u64(1);
+        for _ in 0..100 {
+            let mut picks = StochasticUniversal.select(&[1.0, 1.0, 1.0, 1.0], 4, &mut rng);
+            picks.sort();
+            assert_eq!(picks, vec![0, 1, 2, 3]);
+            // 3/4 of the wheel: exactly 3 of the 4 pointers land on the first ship
+            let picks = StochasticUniversal.select(&[3.0, 1.0], 4, &mut rng);
+            assert_eq!(picks.iter().filter(|i| **i == 0).count(), 3);
+        }
+    }
+}
@@ -0,0 +1,254 @@
+use std::collections::{HashMap};
+
+use rayon::prelude::*;
+
+use super::game::*;
+use super::pilot::*;
+use crate::maths::pos::*;
+
+/* --------------------------------------------------------- */
+/* -   REFERENCE SOLVER   ---------------------------------- */
+/* --------------------------------------------------------- */
+
+// changes of the angle and power the solver tries on each turn
+const ANGLE_MOVES: [i32; 3] = [-15, 0, 15];
+const POWER_MOVES: [i32; 3] = [-1, 0, 1];
+
+struct Node {
+    pos: Pos,
+    h_speed: f32,
+    v_speed: f32,
+    angle: f32,
+    power: f32,
+    fuel: f32,
+    parent: usize,
+    target: (i32, i32)
+}
+
+pub struct Solution {
+    pub commands: Vec<(i32, i32)>, // angle and power of each turn
+    pub fuel: f32,
+    pub expanded: usize
+}
+
+// dynamic programming turn by turn over the states of the ship rounded to
+// `solver_position_step` meters and `solver_speed_step` m/s (the angle and power
+// are kept exact): each cell keeps the state that burnt the least fuel to reach
+// it, a state is dropped when the fuel it burnt plus a lower bound of the fuel it
+// still needs can't beat the best landing found. On each turn the `solver_width`
+// states with the lowest fuel burnt plus lower bound go on (A* order with a cap on
+// the memory), the reference pilot flies from each of them: its landings are
+// landings of the solver too and tighten the pruning. A cell keeps one state on its
+// fuel alone, the others of the cell are lost, so even an unbounded beam is not
+// optimal, only a finer grid gets closer. The search gives up after
+// `solver_max_nodes` states
+pub fn solve(game: &Game) -> Result<Solution, String> {
+    let settings = &game.settings;
+    let position_step = settings.solver_position_step.max(1.0);
+    let speed_step = settings.solver_speed_step.max(0.1);
+    let key = |node: &Node| (
+        (node.pos.x / position_step) as i32, (node.pos.y / position_step) as i32,
+        (node.h_speed / speed_step).round() as i32, (node.v_speed / speed_step).round() as i32,
+        node.angle as i32, node.power as i32
+    );
+    let initial_fuel = game.level_data.fuel;
+    let estimate = |node: &Node| initial_fuel - node.fuel + fuel_lower_bound(game, node);
+
+    let mut nodes = vec![Node {
+        pos: game.level_data.pos.clone(),
+        h_speed: game.level_data.h_speed,
+        v_speed: game.level_data.v_speed,
+        angle: game.level_data.angle,
+        power: game.level_data.power,
+        fuel: initial_fuel,
+        parent: 0,
+        target: (0, 0)
+    }];
+    let mut layer = vec![0];
+    let mut best: Option<(f32, Vec<(i32, i32)>)> = None;
+    let mut expanded = 0;
+
+    while !layer.is_empty() && nodes.len() < settings.solver_max_nodes {
+        let mut children: Vec<Node> = vec![];
+        let mut cells: HashMap<(i32, i32, i32, i32, i32, i32), usize> = HashMap::new();
+        for index in layer.into_iter() {
+            expanded += 1;
+            for angle_move in ANGLE_MOVES.iter() {
+                for power_move in POWER_MOVES.iter() {
+                    let node = &nodes[index];
+                    let target = ((node.angle as i32 + angle_move).clamp(-90, 90), (node.power as i32 + power_move).clamp(0, 4));
+                    let mut ship = ship_at(game, node);
+                    let (angle, power) = (target.0 as f32 - ship.angle, target.1 as f32 - ship.power);
+                    game.step_ship(&mut ship, angle, power);
+                    if ship.is_dead && !ship.is_solution {
+                        continue;
+                    }
+                    let child = Node {
+                        pos: ship.pos.clone(),
+                        h_speed: ship.h_speed,
+                        v_speed: ship.v_speed,
+                        angle: ship.angle,
+                        power: ship.power,
+                        fuel: ship.fuel,
+                        parent: index,
+                        target
+                    };
+                    let best_fuel = best.as_ref().map_or(-1.0, |best| best.0);
+                    if ship.is_solution {
+                        if child.fuel > best_fuel {
+                            let mut plan = commands(&nodes, index);
+                            plan.push(child.target);
+                            best = Some((child.fuel, plan));
+                        }
+                        continue;
+                    }
+                    // not enough fuel left to land, or to beat the best landing
+                    if fuel_lower_bound(game, &child) > child.fuel || initial_fuel - estimate(&child) <= best_fuel {
+                        continue;
+                    }
+                    let cell = key(&child);
+                    match cells.get(&cell) {
+                        Some(other) if children[*other].fuel >= child.fuel => {},
+                        Some(other) => children[*other] = child,
+                        None => {
+                            cells.insert(cell, children.len());
+                            children.push(child);
+                        }
+                    }
+                }
+            }
+        }
+        // A* order under the memory cap: the states that could land with the least
+        // fuel burnt (fuel burnt plus its lower bound) go on to the next turn
+        let mut order = (0..children.len()).collect::<Vec<usize>>();
+        order.sort_by(|a, b| estimate(&children[*a]).total_cmp(&estimate(&children[*b])));
+        let mut kept = order.into_iter().take(settings.solver_width.max(1)).collect::<Vec<usize>>();
+        kept.sort();
+        // the pilot flown from the kept states only raises the best landing, which prunes the others
+        let rollouts = kept.par_iter().map(|i| {
+            fly_pilot_from(game, ship_at(game, &children[*i]), settings.pilot_clearance, settings.policy_max_turns)
+        }).collect::<Vec<Ship>>();
+        for (i, rollout) in kept.iter().zip(rollouts.iter()) {
+            if rollout.is_solution && best.as_ref().is_none_or(|best| rollout.fuel > best.0) {
+                let mut plan = commands(&nodes, children[*i].parent);
+                plan.push(children[*i].target);
+                plan.extend(rollout.chromosome.genes.iter().cloned());
+                best = Some((rollout.fuel, plan));
+            }
+        }
+        let mut children = children.into_iter().map(Some).collect::<Vec<Option<Node>>>();
+        layer = (nodes.len()..nodes.len() + kept.len()).collect::<Vec<usize>>();
+        nodes.extend(kept.into_iter().filter_map(|i| children[i].take()));
+    }
+    return match best {
+        Some((fuel, commands)) => Ok(Solution { commands, fuel, expanded }),
+        None if nodes.len() >= settings.solver_max_nodes => Err(format!("error: the solver reached {} states without landing", nodes.len())),
+        None => Err(String::from("error: the solver found no landing on this grid"))
+    };
+}
+
+// fuel needed to land from `node`, never more than the real need: the thrust is
+// at most the power burnt, so the fuel covers the vertical and horizontal speed
+// changes: the horizontal speed over the landing limit, and the vertical thrust
+// against the gravity during the flight, at least as long as reaching the pad
+// with the whole power pushing sideways
+fn fuel_lower_bound(game: &Game, node: &Node) -> f32 {
+    let distance = (game.landing_zone_xmin - node.pos.x).max(node.pos.x - game.landing_zone_xmax).max(0.0);
+    let toward = if node.pos.x < game.landing_zone_xmin { node.h_speed } else { -node.h_speed };
+    let time = if distance > 0.0 { (-toward + (toward * toward + 8.0 * distance).sqrt()) / 4.0 } else { 0.0 };
+    let horizontal = (node.h_speed.abs() - 20.0).max(0.0);
+    let vertical = brake_fuel(node.pos.y - game.landing_zone_y, node.v_speed, game.gravity)
+        .max(game.gravity * time - 40.0 - node.v_speed)
+        .max(0.0);
+    return (horizontal * horizontal + vertical * vertical).sqrt();
+}
+
+// fuel of the cheapest vertical landing from `height` meters above the pad:
+// free fall then full power until the touchdown at the 40 m/s limit
+fn brake_fuel(height: f32, v_speed: f32, gravity: f32) -> f32 {
+    let deceleration = 4.0 - gravity;
+    if height <= 0.0 || deceleration <= 0.0 {
+        return 0.0;
+    }
+    let top_speed_squared = (height + v_speed * v_speed / (2.0 * gravity) + 1600.0 / (2.0 * deceleration))
+        / (1.0 / (2.0 * gravity) + 1.0 / (2.0 * deceleration));
+    let top_speed = top_speed_squared.max(0.0).sqrt().max(-v_speed.min(0.0));
+    return (4.0 * (top_speed - 40.0) / deceleration).max(0.0);
+}
+
+fn ship_at(game: &Game, node: &Node) -> Ship {
+    let mut ship = Ship::from_chromosome(&game.level_data, Chromosome::from_genes(vec![]));
+    ship.pos = node.pos.clone();
+    ship.h_speed = node.h_speed;
+    ship.v_speed = node.v_speed;
+    ship.angle = node.angle;
+    ship.power = node.power;
+    ship.fuel = node.fuel;
+    return ship;
+}
+
+// commands from the start to the node at `index`
+fn commands(nodes: &[Node], mut index: usize) -> Vec<(i32, i32)> {
+    let mut commands = vec![];
+    while index != 0 {
+        commands.push(nodes[index].target);
+        index = nodes[index].parent;
+    }
+    commands.reverse();
+    return commands;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsing::parser::{parse_file};
+    use crate::parsing::settings::{Settings};
+
+    // the ship before each turn of `commands`, and after the last one
+    fn flight(game: &Game, commands: &[(i32, i32)]) -> Vec<Ship> {
+        let mut ship = Ship::from_chromosome(&game.level_data, Chromosome::from_genes(vec![]));
+        let mut states = vec![ship.clone()];
+        for target in commands.iter() {
+            let (angle, power) = (target.0 as f32 - ship.angle, target.1 as f32 - ship.power);
+            game.step_ship(&mut ship, angle, power);
+            states.push(ship.clone());
+        }
+        return states;
+    }
+
+    fn node(ship: &Ship) -> Node {
+        return Node { pos: ship.pos.clone(), h_speed: ship.h_speed, v_speed: ship.v_speed, angle: ship.angle, power: ship.power, fuel: ship.fuel, parent: 0, target: (0, 0) };
+    }
+
+    #[test]
+    fn the_lower_bound_never_exceeds_the_fuel_of_a_landing() {
+        for level in ["levels/01", "levels/02", "levels/03"].iter() {
+            let game = Game::setup(&parse_file(level).unwrap(), &Settings::default());
+            let pilot = fly_pilot(&game, game.settings.pilot_clearance, game.settings.policy_max_turns);
+            if !pilot.is_solution {
+                continue;
+            }
+            let states = flight(&game, &pilot.chromosome.genes);
+            let landing = states.last().unwrap();
+            assert!(landing.is_solution);
+            for (turn, state) in states.iter().enumerate() {
+                let bound = fuel_lower_bound(&game, &node(state));
+                assert!(bound <= state.fuel - landing.fuel + 1e-3, "{} turn {}: {} > {}", level, turn, bound, state.fuel - landing.fuel);
+            }
+        }
+    }
+
+    #[test]
+    fn the_solver_lands_on_the_first_level() {
+        let level_data = parse_file("levels/01").unwrap();
+        let game = Game::setup(&level_data, &Settings { solver_width: 100, ..Settings::default() });
+        let solution = solve(&game).unwrap();
+        let states = flight(&game, &solution.commands);
+        let landing = states.last().unwrap();
+        assert!(landing.is_solution && states[..states.len() - 1].iter().all(|ship| !ship.is_dead));
+        assert_eq!(landing.fuel, solution.fuel);
+        // the solver keeps the best of its landings and of the pilot flown from the start
+        let pilot = fly_pilot(&game, game.settings.pilot_clearance, game.settings.policy_max_turns);
+        assert!(!pilot.is_solution || solution.fuel >= pilot.fuel);
+    }
+}
@@ -1,3 +1,7 @@
+// explicit returns are the style of the codebase
+#![allow(clippy::needless_return)]
+
+
 extern crate glutin_window;
 extern crate opengl_graphics;
 extern crate piston;
@@ -11,73 +15,54 @@ mod parsing;
 mod display;
 mod maths;
 mod game;
+mod benchmark;
 
 use parsing::args::{Config};
-use parsing::parser::{parse_file};
+use parsing::parser::{parse_file, LevelData};
+use parsing::plans::{parse_plans, write_plans};
 use display::display::*;
 use display::args::*;
 use game::game::*;
-use maths::utils::*;
+use game::islands::*;
+use game::optimizer::*;
+use game::genome::*;
+use game::policy::*;
+use game::neuro::*;
+use game::pilot::*;
+use game::solver::*;
+use game::gp::*;
 
-fn run_genetic(game: &mut Game) {
-    game.turn = 0;
-    while game.turn < CHROMOSOME_SIZE && game.ships.iter().filter(|ship| !ship.is_dead).count() > 0 {
-        // eprintln!("ok");
-        for ship in game.ships.iter_mut().filter(|ship| !ship.is_dead) {
-            let instruction = ship.chromosome.genes[game.turn];
-            let angle: f32 = instruction.0 as f32;
-            let power: f32 = instruction.1 as f32;
-            let prev_pos = ship.pos.clone();
-            ship.simulate(angle, power, game.gravity);
-            for index in 0..(game.map.len() - 1) {
-                // eprintln!("index: {}", index);
-                let a = game.map[index].clone();
-                let b = game.map[index + 1].clone();
+fn run_search(game: &mut Game, optimizer: &mut dyn Optimizer) {
+    optimizer.step(game);
+    game.next_turn = false;
+}
 
-                if do_intersect(&a, &b, &prev_pos, &ship.pos) {
-                    // eprintln!("A");
-                    ship.crash_pos = find_intersection_point(&a, &b, &prev_pos, &ship.pos);
-                    ship.crash_zone_index = index;
-                    if ship.crash_zone_index == game.landing_zone_index && ship.angle == 0.0 && ship.v_speed >= -40.0 && ship.h_speed.abs() <= 20.0
-                    {
-                        if game.best_ship.is_none() || ship.chromosome.fitness > game.best_ship.as_ref().unwrap().chromosome.fitness {
-                            game.best_ship = Some(ship.clone());
-                        }
-                        ship.is_solution = true;
-                    }
-                    ship.is_dead = true;
-                    break;
-                }
-            }
-            if !ship.is_dead && ship.is_out_of_map() { // FIXME, it mean no intersection and out of map, maybe find better way to do that
-                // eprintln!("B");
-                ship.is_out = true;
-                ship.is_dead = true;
-            }
-        }
-        game.turn += 1;
-        game.next_turn = false;
-    }
-    game.evaluate();
-    // for ship in game.ships.iter() {
-    //     eprintln!("{}, is_out: {}", ship.chromosome.fitness, ship.is_out);
-    // }
-    let elites = game.get_elites();
-    game.previous_population = game.ships.clone();
-    game.generate();
-    for i in 0..elites.len() {
-        game.ships[i] = elites[i].clone();
-        game.ships[i].is_elite = true;
-    }
+// trains a controller on the levels of the settings, returns its flight on `game`
+fn train_policy<P: Policy>(config: &Config, level_data: &LevelData, game: &Game) -> Result<Vec<(i32, i32)>, String> {
+    let mut search = PolicySearch::<P>::setup(&config.level_file, level_data, &config.settings)?;
+    let policy = search.train(config.time_limit);
+    return Ok(fly_policy(game, &policy, config.settings.policy_max_turns).chromosome.genes);
 }
 
 fn main() {
     let config: Config = Config::new();
     match parse_file(&config.level_file) {
         Ok(level_data) => {
-            let mut display: Display = Display::setup(7000.0 * SCREEN_SCALE, 3000.0 * SCREEN_SCALE);
-            let mut game: Game = Game::setup(&level_data);
+            rayon::ThreadPoolBuilder::new().num_threads(config.settings.threads).build_global().expect("error: can't build the thread pool");
+            if let Some(generations) = config.benchmark {
+                return benchmark::run(&level_data, &config.settings, generations);
+            }
+            if let Some(evaluations) = config.compare {
+                return benchmark::compare(&level_data, &config.settings, evaluations);
+            }
+            if config.online {
+                return benchmark::online(&level_data, &config.settings, config.time_limit);
+            }
+
+            let mut game: Game = Game::setup(&level_data, &island_settings(&config.settings, 0));
+            let mut optimizer: Box<dyn Optimizer> = config.settings.optimizer.build(&level_data, &config.settings);
             
+            eprintln!("seed: {}", game.seed);
             eprintln!("pos: {:?}", game.level_data.pos);
             eprintln!("angle: {}", game.level_data.angle);
             eprintln!("power: {}", game.level_data.power);
@@ -90,6 +75,67 @@ fn main() {
             eprintln!("landing_zone_index: {}", game.landing_zone_index);
             eprintln!("map: {:#?}", game.map);
 
+            if config.neuro || config.gp {
+                let plan = match config.neuro {
+                    true => train_policy::<Network>(&config, &level_data, &game),
+                    false => train_policy::<Controller>(&config, &level_data, &game)
+                };
+                match plan {
+                    Ok(plan) => {
+                        // the flight of the controller is shown as a plan of targets
+                        game.genome = GenomeKind::Targets.build(&game.settings);
+                        game.replay(plan);
+                    },
+                    Err(error) => {
+                        println!("{}", error);
+                        return;
+                    }
+                }
+            } else if config.pilot {
+                // the flight of the pilot is shown as a plan of targets
+                let ship = fly_pilot(&game, config.settings.pilot_clearance, config.settings.policy_max_turns);
+                eprintln!("pilot: {} turns | landed: {} | fuel: {}", ship.path.len(), ship.is_solution, ship.fuel);
+                game.genome = GenomeKind::Targets.build(&game.settings);
+                game.replay(ship.chromosome.genes);
+            } else if config.solve {
+                match solve(&game) {
+                    Ok(solution) => {
+                        eprintln!("solver: {} states expanded | {} turns | fuel: {}", solution.expanded, solution.commands.len(), solution.fuel);
+                        for (angle, power) in solution.commands.iter() {
+                            println!("{} {}", angle, power);
+                        }
+                        game.genome = GenomeKind::Targets.build(&game.settings);
+                        game.replay(solution.commands);
+                    },
+                    Err(error) => {
+                        println!("{}", error);
+                        return;
+                    }
+                }
+            } else if let Some(ref plans_file) = config.replay {
+                match parse_plans(plans_file) {
+                    Ok((encoding, mut plans)) if config.member < plans.len() => {
+                        // the genes are read with the encoding they were found with
+                        if let Some(encoding) = encoding {
+                            if encoding != config.settings.encoding {
+                                eprintln!("replay: the plans were found with the {:?} encoding, it replaces {:?}", encoding, config.settings.encoding);
+                            }
+                            game.genome = encoding.build(&game.settings);
+                        }
+                        game.replay(plans.swap_remove(config.member));
+                    },
+                    Ok((_, plans)) => {
+                        println!("error: the plans file has {} plans", plans.len());
+                        return;
+                    },
+                    Err(error) => {
+                        println!("{}", error);
+                        return;
+                    }
+                }
+            }
+
+            let mut display: Display = Display::setup(7000.0 * SCREEN_SCALE, 3000.0 * SCREEN_SCALE);
             let mut duration: u128 = 0;
             let mut events = Events::new(EventSettings::new());
             while let Some(e) = events.next(&mut display.window) {
@@ -98,13 +144,26 @@ fn main() {
                     if !game.paused || game.next_turn {
                         if !game.search_ended {
                             let start_time = Instant::now();
-                            run_genetic(&mut game);
+                            run_search(&mut game, &mut *optimizer);
                             duration += start_time.elapsed().as_millis();
+                            let shown_ship = game.best_ship.as_ref().or_else(|| game.previous_population.iter().max_by(|a, b| a.chromosome.fitness.partial_cmp(&b.chromosome.fitness).unwrap()));
+                            if let Some(ship) = shown_ship {
+                                display.set_status(&format!("gen {} | best {}", game.generation, ship.chromosome.breakdown));
+                            }
                             if duration > config.time_limit {
-                                game.search_ended = true;
-                                game.paused = true;
-                                game.turn = 0;
+                                game.polish_best();
+                                game.refine_best();
+                                game.end_search();
                                 eprintln!("generations: {}", game.generation);
+                                let front = game.pareto_front();
+                                for (index, ship) in front.iter().enumerate() {
+                                    eprintln!("front {}: fuel {} | h_speed {:.1} | v_speed {:.1} | turns {}", index, ship.fuel, ship.h_speed, ship.v_speed, ship.path.len());
+                                }
+                                if let Some(ref front_file) = config.export_front {
+                                    if let Err(error) = write_plans(front_file, &front, &game.settings.objectives, game.settings.encoding) {
+                                        println!("{}", error);
+                                    }
+                                }
                             }
                         } else {
                             game.turn += 1;
@@ -117,22 +176,23 @@ fn main() {
                     display.render_ground(&event, &game.map);
                     if !game.search_ended {
                         for ship in game.previous_population.iter() {
-                            display.render_ray(&event, &ship, if ship.is_solution { GREEN } else if ship.is_elite { BLUE } else { RED });
+                            display.render_ray(&event, ship, if ship.is_solution { GREEN } else if ship.is_elite { BLUE } else { RED });
                         }
-                        match game.best_ship {
-                            Some(ref ship) => display.render_ray(&event, ship, GOLD),
-                            None => {}
+                        if let Some(ref ship) = game.best_ship {
+                            display.render_ray(&event, ship, GOLD);
                         }
                     } else {
                         let best_ship: &Ship = game.best_ship.as_ref().unwrap();
                         if game.turn < best_ship.path.len() {
+                            // the ship turns and throttles toward its target at most 15 degrees and 1 per turn
+                            let targets = game.genome.decode(&best_ship.chromosome.genes, game.level_data.angle as i32, game.level_data.power as i32);
                             let mut next_angle = game.level_data.angle as i32;
                             let mut next_power = game.level_data.power as i32;
-                            for i in 0..(game.turn + 1) {
-                                next_angle = (next_angle + (best_ship.chromosome.genes[i].0).min(15).max(-15)).min(90).max(-90);
-                                next_power = (next_power + (best_ship.chromosome.genes[i].1).min(1).max(-1)).min(4).max(0);
+                            for (angle, power) in targets.iter().take(game.turn + 1) {
+                                next_angle += (angle - next_angle).clamp(-15, 15);
+                                next_power += (power - next_power).clamp(-1, 1);
                             }
-                            display.render_ray(&event, &best_ship, GREEN);
+                            display.render_ray(&event, best_ship, GREEN);
                             display.render_ship(&event, &best_ship.path[game.turn], next_angle as f32, next_power as f32);
                         } else {
                             game.turn = 0;
@@ -0,0 +1,141 @@
+// reverse mode automatic differentiation: every operation on a Var is recorded
+// on the tape with the derivative toward each operand, gradient() then walks the
+// tape backward once to get the derivative of one output toward every input
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Var(usize);
+
+#[derive(Debug, Default)]
+pub struct Tape {
+    values: Vec<f64>,
+    parents: Vec<[(usize, f64); 2]> // operand and derivative toward it, usize::MAX when unused
+}
+
+const NONE: (usize, f64) = (usize::MAX, 0.0);
+
+impl Tape {
+    pub fn new() -> Self {
+        return Self::default();
+    }
+
+    fn push(&mut self, value: f64, parents: [(usize, f64); 2]) -> Var {
+        self.values.push(value);
+        self.parents.push(parents);
+        return Var(self.values.len() - 1);
+    }
+
+    pub fn var(&mut self, value: f64) -> Var {
+        return self.push(value, [NONE, NONE]);
+    }
+
+    pub fn value(&self, a: Var) -> f64 {
+        return self.values[a.0];
+    }
+
+    // any function of one variable, given its value and its derivative at `a`
+    pub fn unary(&mut self, a: Var, value: f64, derivative: f64) -> Var {
+        return self.push(value, [(a.0, derivative), NONE]);
+    }
+
+    pub fn add(&mut self, a: Var, b: Var) -> Var {
+        return self.push(self.values[a.0] + self.values[b.0], [(a.0, 1.0), (b.0, 1.0)]);
+    }
+
+    pub fn sub(&mut self, a: Var, b: Var) -> Var {
+        return self.push(self.values[a.0] - self.values[b.0], [(a.0, 1.0), (b.0, -1.0)]);
+    }
+
+    pub fn mul(&mut self, a: Var, b: Var) -> Var {
+        let (va, vb) = (self.values[a.0], self.values[b.0]);
+        return self.push(va * vb, [(a.0, vb), (b.0, va)]);
+    }
+
+    pub fn add_const(&mut self, a: Var, k: f64) -> Var {
+        return self.unary(a, self.values[a.0] + k, 1.0);
+    }
+
+    pub fn scale(&mut self, a: Var, k: f64) -> Var {
+        return self.unary(a, self.values[a.0] * k, k);
+    }
+
+    pub fn sin(&mut self, a: Var) -> Var {
+        let va = self.values[a.0];
+        return self.unary(a, va.sin(), va.cos());
+    }
+
+    pub fn cos(&mut self, a: Var) -> Var {
+        let va = self.values[a.0];
+        return self.unary(a, va.cos(), -va.sin());
+    }
+
+    // max(a, 0) squared, a penalty that is smooth where it starts
+    pub fn hinge_squared(&mut self, a: Var) -> Var {
+        let va = self.values[a.0].max(0.0);
+        return self.unary(a, va * va, 2.0 * va);
+    }
+
+    // derivative of `output` toward every var of the tape
+    pub fn gradient(&self, output: Var) -> Vec<f64> {
+        let mut adjoints = vec![0.0; self.values.len()];
+        adjoints[output.0] = 1.0;
+        for i in (0..=output.0).rev() {
+            let adjoint = adjoints[i];
+            if adjoint == 0.0 {
+                continue;
+            }
+            for (parent, derivative) in self.parents[i].iter() {
+                if *parent != usize::MAX {
+                    adjoints[*parent] += adjoint * derivative;
+                }
+            }
+        }
+        return adjoints;
+    }
+
+    pub fn grad(&self, gradient: &[f64], a: Var) -> f64 {
+        return gradient[a.0];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // sin(a) * b + cos(a * b) - (b + 1) + max(a - b + 1, 0)^2 / 2
+    fn f(tape: &mut Tape, a: Var, b: Var) -> Var {
+        let sin = tape.sin(a);
+        let left = tape.mul(sin, b);
+        let ab = tape.mul(a, b);
+        let cos = tape.cos(ab);
+        let sum = tape.add(left, cos);
+        let b1 = tape.add_const(b, 1.0);
+        let sum = tape.sub(sum, b1);
+        let gap = tape.sub(a, b);
+        let gap = tape.add_const(gap, 1.0);
+        let hinge = tape.hinge_squared(gap);
+        let hinge = tape.scale(hinge, 0.5);
+        return tape.add(sum, hinge);
+    }
+
+    fn value(a: f64, b: f64) -> f64 {
+        let mut tape = Tape::new();
+        let (a, b) = (tape.var(a), tape.var(b));
+        let output = f(&mut tape, a, b);
+        return tape.value(output);
+    }
+
+    #[test]
+    fn gradient_matches_finite_differences() {
+        let eps = 1e-6;
+        for (a0, b0) in [(0.3, 1.2), (1.5, -0.7), (-2.0, 0.4)].iter() {
+            let mut tape = Tape::new();
+            let (a, b) = (tape.var(*a0), tape.var(*b0));
+            let output = f(&mut tape, a, b);
+            let gradient = tape.gradient(output);
+            let da = (value(a0 + eps, *b0) - value(a0 - eps, *b0)) / (2.0 * eps);
+            let db = (value(*a0, b0 + eps) - value(*a0, b0 - eps)) / (2.0 * eps);
+            assert!((tape.grad(&gradient, a) - da).abs() < 1e-6, "d/da: {} vs {}", tape.grad(&gradient, a), da);
+            assert!((tape.grad(&gradient, b) - db).abs() < 1e-6, "d/db: {} vs {}", tape.grad(&gradient, b), db);
+        }
+    }
+}
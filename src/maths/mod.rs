@@ -1,3 +1,5 @@
 pub mod utils;
 pub mod pos;
-pub mod space;
\ No newline at end of file
+pub mod space;
+pub mod visibility;
+pub mod autodiff;
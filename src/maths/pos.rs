@@ -10,8 +10,8 @@ pub struct Pos {
 impl Pos {
     pub fn from(x: f32, y: f32) -> Self {
         return Pos {
-            x: x,
-            y: y
+            x,
+            y
         }
     }
 
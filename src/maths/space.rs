@@ -9,10 +9,10 @@ pub struct Space {
 impl Space {
     pub fn new(x0: f32, x1: f32, y0: f32, y1: f32) -> Self {
         return Space {
-            x0: x0,
-            x1: x1,
-            y0: y0,
-            y1: y1
+            x0,
+            x1,
+            y0,
+            y1
         }
     }
 }
\ No newline at end of file
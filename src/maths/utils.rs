@@ -47,4 +47,34 @@ pub fn find_intersection_point(a: &Pos, b: &Pos, c: &Pos, d: &Pos) -> Pos {
 
 pub fn scale(value: f32, from1: f32, to1: f32, from2: f32, to2: f32) -> f32 {
     return (value - from1) / (to1 - from1) * (to2 - from2) + from2;
-}
\ No newline at end of file
+}
+
+// the ground segment right below `pos`, the highest one when several are under it (caves)
+pub fn segment_below(map: &[Pos], pos: &Pos) -> Option<(usize, f32)> {
+    let mut best: Option<(usize, f32)> = None;
+    for i in 0..(map.len() - 1) {
+        let (a, b) = (&map[i], &map[i + 1]);
+        if pos.x < a.x.min(b.x) || pos.x > a.x.max(b.x) || (b.x - a.x).abs() <= f32::EPSILON {
+            continue;
+        }
+        let y = a.y + (b.y - a.y) * (pos.x - a.x) / (b.x - a.x);
+        if y <= pos.y && best.is_none_or(|(_, best_y)| y > best_y) {
+            best = Some((i, y));
+        }
+    }
+    return best;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segment_below_skips_the_ceiling_of_a_cave() {
+        // floor at 100, a ceiling from 2000 back to 1000 over it
+        let map = vec![Pos::from(0.0, 100.0), Pos::from(3000.0, 100.0), Pos::from(3000.0, 2000.0), Pos::from(1000.0, 1000.0)];
+        assert_eq!(segment_below(&map, &Pos::from(2000.0, 500.0)), Some((0, 100.0)));
+        assert_eq!(segment_below(&map, &Pos::from(2000.0, 2500.0)), Some((2, 1500.0)));
+        assert_eq!(segment_below(&map, &Pos::from(2000.0, 50.0)), None);
+    }
+}
@@ -0,0 +1,158 @@
+use super::pos::*;
+use super::utils::*;
+
+// segments are shortened by this length at both ends before the intersection
+// test, so touching the terrain at an endpoint does not block the view
+const EPSILON: f32 = 0.5;
+
+fn dist(a: &Pos, b: &Pos) -> f32 {
+    return ((a.x - b.x) * (a.x - b.x) + (a.y - b.y) * (a.y - b.y)).sqrt();
+}
+
+/* --------------------------------------------------------- */
+/* -   VISIBILITY GRAPH   ---------------------------------- */
+/* --------------------------------------------------------- */
+
+// shortest path through the air from any point to the landing zone, going
+// around the terrain vertices instead of following the ground
+#[derive(Debug, Clone)]
+pub struct VisibilityGraph {
+    pub map: Vec<Pos>,
+    pub landing_zone_index: usize,
+    pub dist_to_landing: Vec<f32>, // for each terrain vertex
+    pub max_dist: f32
+}
+
+impl VisibilityGraph {
+    pub fn new(map: &[Pos], landing_zone_index: usize) -> Self {
+        let mut graph = Self {
+            map: map.to_vec(),
+            landing_zone_index,
+            dist_to_landing: vec![f32::INFINITY; map.len()],
+            max_dist: 0.0
+        };
+        graph.dist_to_landing = graph.dijkstra();
+        graph.max_dist = graph.dist_to_landing.iter().cloned().filter(|d| d.is_finite()).fold(0.0, f32::max);
+        return graph;
+    }
+
+    // a point is in the air when a ray going up crosses the ground an even number of times
+    pub fn is_in_air(&self, pos: &Pos) -> bool {
+        let top = Pos::from(pos.x, 1e6);
+        let mut crossings = 0;
+        for index in 0..(self.map.len() - 1) {
+            let (a, b) = (&self.map[index], &self.map[index + 1]);
+            if (a.x <= pos.x) != (b.x <= pos.x) && do_intersect(a, b, pos, &top) {
+                crossings += 1;
+            }
+        }
+        return crossings % 2 == 0;
+    }
+
+    fn is_visible(&self, a: &Pos, b: &Pos) -> bool {
+        let length = dist(a, b);
+        if length <= 2.0 * EPSILON {
+            return true;
+        }
+        let t = EPSILON / length;
+        let a0 = Pos::from(a.x + (b.x - a.x) * t, a.y + (b.y - a.y) * t);
+        let b0 = Pos::from(b.x + (a.x - b.x) * t, b.y + (a.y - b.y) * t);
+        for index in 0..(self.map.len() - 1) {
+            if do_intersect(&self.map[index], &self.map[index + 1], &a0, &b0) {
+                return false;
+            }
+        }
+        return self.is_in_air(&Pos::from((a.x + b.x) / 2.0, (a.y + b.y) / 2.0));
+    }
+
+    // vertices `i` and `j` are linked by the ground itself when they are neighbours
+    fn are_vertices_visible(&self, i: usize, j: usize) -> bool {
+        return i + 1 == j || j + 1 == i || self.is_visible(&self.map[i], &self.map[j]);
+    }
+
+    // straight line down (or sideways) to the closest point of the landing zone
+    fn direct_dist(&self, pos: &Pos) -> f32 {
+        let (a, b) = (&self.map[self.landing_zone_index], &self.map[self.landing_zone_index + 1]);
+        let target = Pos::from(pos.x.max(a.x.min(b.x)).min(a.x.max(b.x)), a.y);
+        if self.is_visible(pos, &target) {
+            return dist(pos, &target);
+        }
+        return f32::INFINITY;
+    }
+
+    fn dijkstra(&self) -> Vec<f32> {
+        let count = self.map.len();
+        let mut dist_to_landing = (0..count).map(|i| {
+            match i == self.landing_zone_index || i == self.landing_zone_index + 1 {
+                true => 0.0,
+                false => self.direct_dist(&self.map[i])
+            }
+        }).collect::<Vec<f32>>();
+        let mut visited = vec![false; count];
+        for _ in 0..count {
+            let current = (0..count).filter(|i| !visited[*i]).min_by(|a, b| dist_to_landing[*a].partial_cmp(&dist_to_landing[*b]).unwrap());
+            let current = match current {
+                Some(current) if dist_to_landing[current].is_finite() => current,
+                _ => break
+            };
+            visited[current] = true;
+            for next in 0..count {
+                if !visited[next] && self.are_vertices_visible(current, next) {
+                    let d = dist_to_landing[current] + dist(&self.map[current], &self.map[next]);
+                    if d < dist_to_landing[next] {
+                        dist_to_landing[next] = d;
+                    }
+                }
+            }
+        }
+        return dist_to_landing;
+    }
+
+    // `segment_index` is the ground segment `pos` lies on, if any
+    pub fn distance(&self, pos: &Pos, segment_index: Option<usize>) -> f32 {
+        let mut best = self.direct_dist(pos);
+        for i in 0..self.map.len() {
+            if self.dist_to_landing[i].is_finite() && self.dist_to_landing[i] < best {
+                let on_same_segment = segment_index.is_some_and(|index| i == index || i == index + 1);
+                if on_same_segment || self.is_visible(pos, &self.map[i]) {
+                    best = best.min(dist(pos, &self.map[i]) + self.dist_to_landing[i]);
+                }
+            }
+        }
+        return best;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // flat landing zone on the left, a peak in the middle, a valley on the right
+    fn graph() -> VisibilityGraph {
+        let map = vec![Pos::from(0.0, 100.0), Pos::from(1000.0, 100.0), Pos::from(1500.0, 2000.0), Pos::from(2500.0, 200.0), Pos::from(4000.0, 400.0)];
+        return VisibilityGraph::new(&map, 0);
+    }
+
+    #[test]
+    fn straight_line_when_the_landing_zone_is_in_sight() {
+        let graph = graph();
+        assert!((graph.distance(&Pos::from(500.0, 1000.0), None) - 900.0).abs() < 1e-3);
+        assert!((graph.distance(&Pos::from(1200.0, 1000.0), None) - dist(&Pos::from(1200.0, 1000.0), &Pos::from(1000.0, 100.0))).abs() < 1e-3);
+    }
+
+    #[test]
+    fn around_the_peak_when_it_hides_the_landing_zone() {
+        let graph = graph();
+        let (pos, peak) = (Pos::from(3000.0, 600.0), Pos::from(1500.0, 2000.0));
+        let expected = dist(&pos, &peak) + dist(&peak, &Pos::from(1000.0, 100.0));
+        assert!((graph.distance(&pos, None) - expected).abs() < 1e-2);
+        assert!((graph.max_dist - graph.dist_to_landing[4]).abs() < 1e-3);
+    }
+
+    #[test]
+    fn below_the_ground_is_not_in_the_air() {
+        let graph = graph();
+        assert!(graph.is_in_air(&Pos::from(1500.0, 2100.0)));
+        assert!(!graph.is_in_air(&Pos::from(1500.0, 1900.0)));
+    }
+}
@@ -1,9 +1,37 @@
-use clap::{Arg, App};
+use clap::{Arg, App, ArgMatches, Error, ErrorKind};
+
+use super::settings::*;
 
 #[derive(Debug, PartialEq)]
 pub struct Config {
     pub level_file: String,
     pub time_limit: u128,
+    pub settings: Settings,
+    pub export_front: Option<String>,
+    pub replay: Option<String>,
+    pub member: usize,
+    pub benchmark: Option<usize>,
+    pub compare: Option<usize>,
+    pub neuro: bool,
+    pub gp: bool,
+    pub pilot: bool,
+    pub solve: bool,
+    pub online: bool,
+}
+
+// reports the problem the way clap reports its own errors, then exits
+fn exit_with(message: &str) -> ! {
+    Error::with_description(message.trim_start_matches("error: "), ErrorKind::InvalidValue).exit();
+}
+
+fn parse_value<T: std::str::FromStr>(name: &str, raw: &str) -> T {
+    return raw.parse::<T>().unwrap_or_else(|_| exit_with(&format!("invalid value for {}: {}", name, raw)));
+}
+
+fn override_value<T: std::str::FromStr>(matches: &ArgMatches, name: &str, value: &mut T) {
+    if let Some(raw) = matches.value_of(name) {
+        *value = parse_value::<T>(name, raw);
+    }
 }
 
 impl Config {
@@ -18,10 +46,259 @@ impl Config {
             .arg(Arg::with_name("time_limit")
                 .required(true)
                 .help("time limit in ms"))
+            .arg(Arg::with_name("config")
+                .long("config")
+                .short("c")
+                .takes_value(true)
+                .help("toml file with the genetic algorithm settings"))
+            .arg(Arg::with_name("optimizer")
+                .long("optimizer")
+                .takes_value(true)
+                .possible_values(&["ga", "random", "annealing", "cmaes", "de", "beam"])
+                .help("search algorithm, the genetic algorithm, a baseline, an evolution strategy or a planner"))
+            .arg(Arg::with_name("mode")
+                .long("mode")
+                .takes_value(true)
+                .possible_values(&["single", "nsga2"])
+                .help("single objective or multi-objective (nsga2) search"))
+            .arg(Arg::with_name("export_front")
+                .long("export-front")
+                .takes_value(true)
+                .help("file to write the pareto front of landings to when the search ends"))
+            .arg(Arg::with_name("replay")
+                .long("replay")
+                .takes_value(true)
+                .help("plans file (see --export-front) to replay instead of searching"))
+            .arg(Arg::with_name("member")
+                .long("member")
+                .takes_value(true)
+                .requires("replay")
+                .help("index of the plan to replay, default 0"))
+            .arg(Arg::with_name("benchmark")
+                .long("benchmark")
+                .takes_value(true)
+                .help("runs this many generations on one thread then on all threads and compares them"))
+            .arg(Arg::with_name("compare")
+                .long("compare")
+                .takes_value(true)
+                .conflicts_with("benchmark")
+                .help("runs every optimizer until it has flown this many ships and compares them"))
+            .arg(Arg::with_name("neuro")
+                .long("neuro")
+                .help("trains a neural network controller instead of searching a plan"))
+            .arg(Arg::with_name("gp")
+                .long("gp")
+                .conflicts_with("neuro")
+                .help("evolves a controller made of expression trees instead of searching a plan"))
+            .arg(Arg::with_name("pilot")
+                .long("pilot")
+                .conflicts_with_all(&["neuro", "gp"])
+                .help("flies the reference pilot instead of searching a plan"))
+            .arg(Arg::with_name("solve")
+                .long("solve")
+                .conflicts_with_all(&["neuro", "gp", "pilot"])
+                .help("searches a near-optimal plan with the reference solver instead of the genetic algorithm"))
+            .arg(Arg::with_name("online")
+                .long("online")
+                .conflicts_with_all(&["benchmark", "compare", "neuro", "gp", "pilot", "solve"])
+                .help("flies the level turn by turn, replanning on each turn, and compares it with the plan of the first turn"))
+            .arg(Arg::with_name("online_turn_ms")
+                .long("online-turn-ms")
+                .takes_value(true)
+                .help("time in ms the online search gets on each turn after the first"))
+            .arg(Arg::with_name("online_referee")
+                .long("referee")
+                .takes_value(true)
+                .possible_values(&["exact", "rounding"])
+                .help("state the online referee sends, exact or rounded to integers"))
+            .arg(Arg::with_name("online_noise")
+                .long("online-noise")
+                .takes_value(true)
+                .help("standard deviation in m/s of the push the online referee gives each speed on each turn"))
+            .arg(Arg::with_name("drift_threshold")
+                .long("drift-threshold")
+                .takes_value(true)
+                .help("drift in meters plus m/s from the predicted state past which the elites of the online search are flown again"))
+            .arg(Arg::with_name("online_max_turns")
+                .long("online-max-turns")
+                .takes_value(true)
+                .help("number of turns the online flight plays at most"))
+            .arg(Arg::with_name("pilot_seeds")
+                .long("pilot-seeds")
+                .takes_value(true)
+                .help("number of ships of the first generation flying the reference pilot or a mutation of it"))
+            .arg(Arg::with_name("levels")
+                .long("levels")
+                .takes_value(true)
+                .help("comma separated level files the controller is trained on"))
+            .arg(Arg::with_name("seed")
+                .long("seed")
+                .takes_value(true)
+                .help("random seed, a run is reproducible with the same seed and settings"))
+            .arg(Arg::with_name("threads")
+                .long("threads")
+                .takes_value(true)
+                .help("number of threads, 0 uses every core"))
+            .arg(Arg::with_name("population_count")
+                .long("population")
+                .takes_value(true)
+                .help("number of ships per generation"))
+            .arg(Arg::with_name("chromosome_size")
+                .long("chromosome-size")
+                .takes_value(true)
+                .help("number of genes (turns) per chromosome"))
+            .arg(Arg::with_name("length_mutation_rate")
+                .long("length-mutation-rate")
+                .takes_value(true)
+                .help("probability for a child to gain or lose a gene, between 0 and 1"))
+            .arg(Arg::with_name("encoding")
+                .long("encoding")
+                .takes_value(true)
+                .possible_values(&["delta", "segments", "targets"])
+                .help("meaning of the genes"))
+            .arg(Arg::with_name("elite_percentage")
+                .long("elite-percentage")
+                .takes_value(true)
+                .help("part of the population kept as is, between 0 and 1"))
+            .arg(Arg::with_name("mutation_rate")
+                .long("mutation-rate")
+                .takes_value(true)
+                .help("probability for a gene to mutate, between 0 and 1"))
+            .arg(Arg::with_name("selection")
+                .long("selection")
+                .takes_value(true)
+                .possible_values(&["roulette", "tournament", "rank", "sus"])
+                .help("parents selection strategy"))
+            .arg(Arg::with_name("tournament_size")
+                .long("tournament-size")
+                .takes_value(true)
+                .help("number of ships competing in a tournament"))
+            .arg(Arg::with_name("crossover")
+                .long("crossover")
+                .takes_value(true)
+                .possible_values(&["blend", "arithmetic", "single_point", "two_point", "uniform", "death", "cut_splice"])
+                .help("crossover operator"))
+            .arg(Arg::with_name("mutation")
+                .long("mutation")
+                .takes_value(true)
+                .possible_values(&["uniform", "creep", "gaussian", "block", "swap"])
+                .help("mutation operator"))
+            .arg(Arg::with_name("mutation_schedule")
+                .long("mutation-schedule")
+                .takes_value(true)
+                .possible_values(&["fixed", "adaptive"])
+                .help("fixed mutation rate or adapted to diversity and stagnation"))
+            .arg(Arg::with_name("distance_metric")
+                .long("distance")
+                .takes_value(true)
+                .possible_values(&["surface", "free_space"])
+                .help("distance to the landing zone used by the fitness"))
+            .arg(Arg::with_name("niching")
+                .long("niching")
+                .takes_value(true)
+                .possible_values(&["none", "sharing", "crowding", "restricted"])
+                .help("diversity preservation"))
+            .arg(Arg::with_name("diversity_metric")
+                .long("diversity-metric")
+                .takes_value(true)
+                .possible_values(&["genotype", "behaviour"])
+                .help("distance between two ships used by the niching"))
+            .arg(Arg::with_name("restart")
+                .long("restart")
+                .takes_value(true)
+                .possible_values(&["none", "partial", "hypermutation", "full"])
+                .help("response to a stagnating search"))
+            .arg(Arg::with_name("restart_after")
+                .long("restart-after")
+                .takes_value(true)
+                .help("generations without improvement before a restart"))
+            .arg(Arg::with_name("gradient_steps")
+                .long("gradient-steps")
+                .takes_value(true)
+                .help("gradient descent steps on the fuel of the best landing when the search ends"))
+            .arg(Arg::with_name("local_search")
+                .long("local-search")
+                .takes_value(true)
+                .possible_values(&["none", "hill_climb", "coordinate"])
+                .help("local search on the landings of the elites"))
+            .arg(Arg::with_name("repair_turns")
+                .long("repair-turns")
+                .takes_value(true)
+                .help("turns rewritten before the touchdown of a near miss, 0 disables the repair"))
+            .arg(Arg::with_name("island_count")
+                .long("islands")
+                .takes_value(true)
+                .help("number of populations evolving side by side"))
+            .arg(Arg::with_name("topology")
+                .long("topology")
+                .takes_value(true)
+                .possible_values(&["ring", "full"])
+                .help("where the islands send their best ships"))
+            .arg(Arg::with_name("migration_interval")
+                .long("migration-interval")
+                .takes_value(true)
+                .help("generations between two migrations"))
             .get_matches();
+
+        let mut settings = match matches.value_of("config") {
+            Some(config_file) => Settings::from_file(config_file).unwrap_or_else(|e| exit_with(&e)),
+            None => Settings::default()
+        };
+        if let Some(seed) = matches.value_of("seed") {
+            settings.seed = Some(parse_value::<u64>("seed", seed));
+        }
+        if let Some(levels) = matches.value_of("levels") {
+            settings.policy_levels = levels.split(',').map(|file| file.trim().to_string()).filter(|file| !file.is_empty()).collect::<Vec<String>>();
+        }
+        override_value(&matches, "threads", &mut settings.threads);
+        override_value(&matches, "pilot_seeds", &mut settings.pilot_seeds);
+        override_value(&matches, "optimizer", &mut settings.optimizer);
+        override_value(&matches, "mode", &mut settings.mode);
+        override_value(&matches, "population_count", &mut settings.population_count);
+        override_value(&matches, "chromosome_size", &mut settings.chromosome_size);
+        override_value(&matches, "length_mutation_rate", &mut settings.length_mutation_rate);
+        override_value(&matches, "encoding", &mut settings.encoding);
+        override_value(&matches, "elite_percentage", &mut settings.elite_percentage);
+        override_value(&matches, "mutation_rate", &mut settings.mutation_rate);
+        override_value(&matches, "selection", &mut settings.selection);
+        override_value(&matches, "tournament_size", &mut settings.tournament_size);
+        override_value(&matches, "crossover", &mut settings.crossover);
+        override_value(&matches, "mutation", &mut settings.mutation);
+        override_value(&matches, "mutation_schedule", &mut settings.mutation_schedule);
+        override_value(&matches, "distance_metric", &mut settings.distance_metric);
+        override_value(&matches, "niching", &mut settings.niching);
+        override_value(&matches, "diversity_metric", &mut settings.diversity_metric);
+        override_value(&matches, "restart", &mut settings.restart);
+        override_value(&matches, "restart_after", &mut settings.restart_after);
+        override_value(&matches, "local_search", &mut settings.local_search);
+        override_value(&matches, "gradient_steps", &mut settings.gradient_steps);
+        override_value(&matches, "online_turn_ms", &mut settings.online_turn_ms);
+        override_value(&matches, "online_referee", &mut settings.online_referee);
+        override_value(&matches, "online_noise", &mut settings.online_noise);
+        override_value(&matches, "drift_threshold", &mut settings.drift_threshold);
+        override_value(&matches, "online_max_turns", &mut settings.online_max_turns);
+        override_value(&matches, "repair_turns", &mut settings.repair_turns);
+        override_value(&matches, "island_count", &mut settings.island_count);
+        override_value(&matches, "topology", &mut settings.topology);
+        override_value(&matches, "migration_interval", &mut settings.migration_interval);
+        if let Err(error) = settings.validate() {
+            exit_with(&error);
+        }
+
         return Self {
             level_file: matches.value_of("file").unwrap_or("").to_string(),
-            time_limit: matches.value_of("time_limit").unwrap_or("").to_string().parse::<u128>().unwrap()
+            time_limit: parse_value::<u128>("time_limit", matches.value_of("time_limit").unwrap_or("")),
+            settings,
+            export_front: matches.value_of("export_front").map(|file| file.to_string()),
+            replay: matches.value_of("replay").map(|file| file.to_string()),
+            member: matches.value_of("member").map(|member| parse_value::<usize>("member", member)).unwrap_or(0),
+            benchmark: matches.value_of("benchmark").map(|generations| parse_value::<usize>("benchmark", generations)),
+            compare: matches.value_of("compare").map(|evaluations| parse_value::<usize>("compare", evaluations)),
+            neuro: matches.is_present("neuro"),
+            gp: matches.is_present("gp"),
+            pilot: matches.is_present("pilot"),
+            solve: matches.is_present("solve"),
+            online: matches.is_present("online")
         }
     }
-}
\ No newline at end of file
+}
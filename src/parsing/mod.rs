@@ -1,2 +1,4 @@
 pub mod args;
-pub mod parser;
\ No newline at end of file
+pub mod parser;
+pub mod settings;
+pub mod plans;
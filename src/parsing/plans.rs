@@ -0,0 +1,68 @@
+use std::fs;
+
+use crate::game::game::{Ship};
+use crate::game::genome::{GenomeKind};
+use crate::game::nsga::{Objective, objectives};
+
+// a header with the encoding the genes are read with, then one plan per line:
+// the objective values, then the genes
+// e.g. "# encoding: delta; [Fuel, Margin];angle power,..."
+//      "512 0.35;-15 1,-3 0,0 1"
+pub fn write_plans(plans_file: &str, ships: &[Ship], objective_list: &[Objective], encoding: GenomeKind) -> Result<(), String> {
+    let mut content = format!("# encoding: {}; {:?};angle power,...\n", format!("{:?}", encoding).to_lowercase(), objective_list);
+    for ship in ships.iter() {
+        let values = objectives(ship, objective_list).iter().map(|value| value.to_string()).collect::<Vec<String>>();
+        let genes = ship.chromosome.genes.iter().map(|gene| format!("{} {}", gene.0, gene.1)).collect::<Vec<String>>();
+        content += &format!("{};{}\n", values.join(" "), genes.join(","));
+    }
+    return fs::write(plans_file, content).map_err(|e| format!("error: can't write plans file: {}", e));
+}
+
+// the genes of a plan, angle and power
+pub type Plan = Vec<(i32, i32)>;
+
+// the encoding of the header, if the file has one, and the plans
+pub fn parse_plans(plans_file: &str) -> Result<(Option<GenomeKind>, Vec<Plan>), String> {
+    let content = fs::read_to_string(plans_file).map_err(|e| format!("error: can't read plans file: {}", e))?;
+    let mut encoding = None;
+    if let Some(header) = content.lines().find(|line| line.starts_with('#')) {
+        if let Some(name) = header.trim_start_matches('#').trim().strip_prefix("encoding:") {
+            encoding = Some(name.split(';').next().unwrap_or("").trim().parse::<GenomeKind>()?);
+        }
+    }
+    let mut plans = vec![];
+    for line in content.lines().filter(|line| !line.trim().is_empty() && !line.starts_with('#')) {
+        let genes = line.rsplit(';').next().unwrap_or("");
+        let mut plan = vec![];
+        for gene in genes.split(',') {
+            let values = gene.split_whitespace().map(|value| value.parse::<i32>()).collect::<Result<Vec<i32>, _>>()
+                .map_err(|_| format!("error: invalid gene in plans file: {}", gene))?;
+            if values.len() != 2 {
+                return Err(format!("error: invalid gene in plans file: {}", gene));
+            }
+            plan.push((values[0], values[1]));
+        }
+        plans.push(plan);
+    }
+    return Ok((encoding, plans));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_encoding_is_read_back_from_the_header() {
+        // one file per process and test, removed before checking so a failure leaves nothing behind
+        let plans_file = std::env::temp_dir().join(format!("cg_mars_lander_ga_{}_the_encoding_is_read_back_from_the_header.txt", std::process::id()));
+        let plans_file = plans_file.to_str().unwrap();
+        fs::write(plans_file, "# encoding: segments; [Fuel];angle power,...\n512;-15 1,3 2\n").unwrap();
+        let with_header = parse_plans(plans_file);
+        // files written before the header had the encoding
+        fs::write(plans_file, "# [Fuel];angle power,...\n512;0 4\n").unwrap();
+        let without_header = parse_plans(plans_file);
+        fs::remove_file(plans_file).unwrap();
+        assert_eq!(with_header, Ok((Some(GenomeKind::Segments), vec![vec![(-15, 1), (3, 2)]])));
+        assert_eq!(without_header, Ok((None, vec![vec![(0, 4)]])));
+    }
+}
@@ -0,0 +1,263 @@
+use std::fs;
+
+use serde::Deserialize;
+
+use crate::game::selection::{SelectionKind};
+use crate::game::crossover::{CrossoverKind};
+use crate::game::mutation::{MutationKind, MutationSchedule};
+use crate::game::fitness::{FitnessWeights, DistanceMetric};
+use crate::game::game::{Mode};
+use crate::game::nsga::{Objective};
+use crate::game::islands::{Topology, IslandSettings};
+use crate::game::diversity::{DiversityMetric, Niching};
+use crate::game::restart::{Restart};
+use crate::game::local_search::{LocalSearch};
+use crate::game::genome::{GenomeKind};
+use crate::game::optimizer::{OptimizerKind};
+use crate::game::online::{Referee};
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub seed: Option<u64>,
+    pub threads: usize,
+    pub optimizer: OptimizerKind,
+    pub annealing_temperature: f32,
+    pub annealing_cooling: f32,
+    pub cmaes_sigma: f32,
+    pub de_f: f32,
+    pub de_cr: f32,
+    pub planner_width: usize,
+    pub planner_macro_turns: usize,
+    pub mode: Mode,
+    pub objectives: Vec<Objective>,
+    pub population_count: usize,
+    pub chromosome_size: usize,
+    pub encoding: GenomeKind,
+    pub segment_max_turns: usize,
+    pub chromosome_max_size: usize,
+    pub length_mutation_rate: f32,
+    pub elite_percentage: f32,
+    pub mutation_rate: f32,
+    pub selection: SelectionKind,
+    pub tournament_size: usize,
+    pub rank_pressure: f32,
+    pub crossover: CrossoverKind,
+    pub crossover_weight: f32,
+    pub crossover_death_backoff: usize,
+    pub mutation: MutationKind,
+    pub mutation_step: i32,
+    pub mutation_sigma: f32,
+    pub mutation_block_size: usize,
+    pub mutation_schedule: MutationSchedule,
+    pub mutation_rate_max: f32,
+    pub diversity_target: f32,
+    pub stagnation_window: usize,
+    pub distance_metric: DistanceMetric,
+    pub niching: Niching,
+    pub diversity_metric: DiversityMetric,
+    pub niche_radius: f32,
+    pub sharing_alpha: f32,
+    pub mating_candidates: usize,
+    pub restart: Restart,
+    pub restart_after: usize,
+    pub restart_fraction: f32,
+    pub hypermutation_rate: f32,
+    pub hypermutation_generations: usize,
+    pub hall_of_fame_size: usize,
+    pub local_search: LocalSearch,
+    pub local_search_elites: usize,
+    pub local_search_steps: usize,
+    pub local_search_end_steps: usize,
+    pub gradient_steps: usize,
+    pub gradient_rate: f32,
+    pub online_turn_ms: u64,
+    pub online_referee: Referee,
+    pub online_noise: f32,
+    pub drift_threshold: f32,
+    pub online_max_turns: usize,
+    pub repair_turns: usize,
+    pub policy_levels: Vec<String>,
+    pub policy_max_turns: usize,
+    pub neuro_hidden: usize,
+    pub neuro_mutation_rate: f32,
+    pub neuro_sigma: f32,
+    pub gp_init_depth: usize,
+    pub gp_max_depth: usize,
+    pub gp_max_size: usize,
+    pub gp_mutation_rate: f32,
+    pub gp_parsimony: f32,
+    pub pilot_clearance: f32,
+    pub pilot_seeds: usize,
+    pub solver_position_step: f32,
+    pub solver_speed_step: f32,
+    pub solver_max_nodes: usize,
+    pub solver_width: usize,
+    pub island_count: usize,
+    pub topology: Topology,
+    pub migration_interval: usize,
+    pub migration_size: usize,
+    pub fitness: FitnessWeights,
+    pub islands: Vec<IslandSettings>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        return Self {
+            seed: None,
+            threads: 0,
+            optimizer: OptimizerKind::Ga,
+            annealing_temperature: 10.0,
+            annealing_cooling: 0.99,
+            cmaes_sigma: 0.3,
+            de_f: 0.5,
+            de_cr: 0.9,
+            planner_width: 10,
+            planner_macro_turns: 5,
+            mode: Mode::Single,
+            objectives: vec![Objective::Fuel, Objective::Margin],
+            population_count: 100,
+            chromosome_size: 180,
+            encoding: GenomeKind::Delta,
+            segment_max_turns: 10,
+            chromosome_max_size: 400,
+            length_mutation_rate: 0.0,
+            elite_percentage: 0.12,
+            mutation_rate: 0.01,
+            selection: SelectionKind::Roulette,
+            tournament_size: 3,
+            rank_pressure: 1.5,
+            crossover: CrossoverKind::Blend,
+            crossover_weight: 0.5,
+            crossover_death_backoff: 10,
+            mutation: MutationKind::Uniform,
+            mutation_step: 3,
+            mutation_sigma: 4.0,
+            mutation_block_size: 10,
+            mutation_schedule: MutationSchedule::Fixed,
+            mutation_rate_max: 0.1,
+            diversity_target: 0.3,
+            stagnation_window: 20,
+            distance_metric: DistanceMetric::Surface,
+            niching: Niching::None,
+            diversity_metric: DiversityMetric::Genotype,
+            niche_radius: 0.1,
+            sharing_alpha: 1.0,
+            mating_candidates: 5,
+            restart: Restart::None,
+            restart_after: 50,
+            restart_fraction: 0.5,
+            hypermutation_rate: 0.2,
+            hypermutation_generations: 10,
+            hall_of_fame_size: 5,
+            local_search: LocalSearch::None,
+            local_search_elites: 2,
+            local_search_steps: 30,
+            local_search_end_steps: 2000,
+            gradient_steps: 0,
+            gradient_rate: 0.03,
+            online_turn_ms: 100,
+            online_referee: Referee::Exact,
+            online_noise: 0.0,
+            drift_threshold: 2.0,
+            online_max_turns: 500,
+            repair_turns: 0,
+            policy_levels: vec![],
+            policy_max_turns: 500,
+            neuro_hidden: 8,
+            neuro_mutation_rate: 0.1,
+            neuro_sigma: 0.3,
+            gp_init_depth: 4,
+            gp_max_depth: 8,
+            gp_max_size: 40,
+            gp_mutation_rate: 0.3,
+            gp_parsimony: 0.05,
+            pilot_clearance: 150.0,
+            pilot_seeds: 0,
+            solver_position_step: 10.0,
+            solver_speed_step: 1.0,
+            solver_max_nodes: 2000000,
+            solver_width: 500,
+            island_count: 1,
+            topology: Topology::Ring,
+            migration_interval: 20,
+            migration_size: 2,
+            fitness: FitnessWeights::default(),
+            islands: vec![]
+        }
+    }
+}
+
+impl Settings {
+    pub fn from_file(config_file: &str) -> Result<Settings, String> {
+        let content = fs::read_to_string(config_file).map_err(|e| format!("error: can't read config file: {}", e))?;
+        return toml::from_str::<Settings>(&content).map_err(|e| format!("error: invalid config file: {}", e));
+    }
+
+    // values the search can't run with, checked once the file and the arguments are read
+    pub fn validate(&self) -> Result<(), String> {
+        let mut problems = vec![];
+        let mut probabilities = vec![
+            (String::from("mutation_rate"), self.mutation_rate),
+            (String::from("mutation_rate_max"), self.mutation_rate_max),
+            (String::from("hypermutation_rate"), self.hypermutation_rate),
+            (String::from("length_mutation_rate"), self.length_mutation_rate),
+            (String::from("elite_percentage"), self.elite_percentage),
+            (String::from("restart_fraction"), self.restart_fraction),
+            (String::from("de_cr"), self.de_cr),
+            (String::from("crossover_weight"), self.crossover_weight),
+            (String::from("neuro_mutation_rate"), self.neuro_mutation_rate),
+            (String::from("gp_mutation_rate"), self.gp_mutation_rate)
+        ];
+        for (index, island) in self.islands.iter().enumerate() {
+            if let Some(rate) = island.mutation_rate {
+                probabilities.push((format!("islands[{}].mutation_rate", index), rate));
+            }
+        }
+        for (name, value) in probabilities.iter() {
+            if !(0.0..=1.0).contains(value) {
+                problems.push(format!("{} must be between 0 and 1, got {}", name, value));
+            }
+        }
+        let counts = [
+            ("population_count", self.population_count),
+            ("chromosome_size", self.chromosome_size),
+            ("tournament_size", self.tournament_size),
+            ("planner_width", self.planner_width),
+            ("planner_macro_turns", self.planner_macro_turns),
+            ("online_max_turns", self.online_max_turns)
+        ];
+        for (name, value) in counts.iter() {
+            if *value == 0 {
+                problems.push(format!("{} must be at least 1", name));
+            }
+        }
+        for (name, value) in [("annealing_temperature", self.annealing_temperature), ("cmaes_sigma", self.cmaes_sigma), ("de_f", self.de_f)].iter() {
+            if value.is_nan() || *value <= 0.0 {
+                problems.push(format!("{} must be above 0, got {}", name, value));
+            }
+        }
+        if self.annealing_cooling <= 0.0 || !(0.0..=1.0).contains(&self.annealing_cooling) {
+            problems.push(format!("annealing_cooling must be above 0 and at most 1, got {}", self.annealing_cooling));
+        }
+        if !(1.0..=2.0).contains(&self.rank_pressure) {
+            problems.push(format!("rank_pressure must be between 1 and 2, got {}", self.rank_pressure));
+        }
+        // the repair rewrites the plan turn by turn
+        if self.repair_turns > 0 && self.encoding == GenomeKind::Segments {
+            problems.push(String::from("the repair needs one gene per turn, set repair_turns to 0 or use the delta or targets encoding"));
+        }
+        // the pilot plan is seeded turn by turn
+        if self.pilot_seeds > 0 && self.encoding == GenomeKind::Segments {
+            problems.push(String::from("the pilot seeds need one gene per turn, set pilot_seeds to 0 or use the delta or targets encoding"));
+        }
+        // the beam planner writes one gene per turn of its plans
+        if self.optimizer == OptimizerKind::Beam && self.encoding == GenomeKind::Segments {
+            problems.push(String::from("the beam optimizer needs one gene per turn, use the delta or targets encoding"));
+        }
+        if problems.is_empty() {
+            return Ok(());
+        }
+        return Err(format!("error: invalid settings: {}", problems.join(", ")));
+    }
+}